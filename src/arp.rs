@@ -0,0 +1,147 @@
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+#[cfg(target_os = "linux")]
+use std::process::Command;
+
+use wol::MacAddr;
+
+// a complete entry of the local ARP cache
+#[derive(Debug, Clone)]
+pub struct ArpEntry {
+    pub ip: Ipv4Addr,
+    pub mac: MacAddr,
+}
+
+// read the ARP cache, only Linux exposes it in a readable form
+#[cfg(target_os = "linux")]
+pub fn read_arp_table() -> io::Result<Vec<ArpEntry>> {
+    let content = std::fs::read_to_string("/proc/net/arp")?;
+    Ok(parse_proc_net_arp(&content))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read_arp_table() -> io::Result<Vec<ArpEntry>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "reading the ARP cache is only supported on Linux",
+    ))
+}
+
+// IP address       HW type     Flags       HW address            Mask     Device
+// 192.168.1.1      0x1         0x2         00:11:22:33:44:55     *        eth0
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_proc_net_arp(content: &str) -> Vec<ArpEntry> {
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 6 {
+                return None;
+            }
+            // flag 0x2 is ATF_COM, the entry is resolved
+            let flags = u32::from_str_radix(fields[2].trim_start_matches("0x"), 16).ok()?;
+            if flags & 0x2 == 0 {
+                return None;
+            }
+            Some(ArpEntry {
+                ip: fields[0].parse().ok()?,
+                mac: fields[3].parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+// make the kernel resolve `ip` by sending it an empty datagram on the discard
+// port, the ARP request goes out before the datagram does
+pub fn probe(ip: Ipv4Addr) -> io::Result<()> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.send_to(&[], SocketAddr::new(ip.into(), 9))?;
+    Ok(())
+}
+
+pub fn lookup(table: &[ArpEntry], ip: Ipv4Addr) -> Option<&ArpEntry> {
+    table.iter().find(|entry| entry.ip == ip)
+}
+
+// permanent entries added so unicast sends to a sleeping device can be
+// resolved, they are removed again by `cleanup`
+pub struct StaticEntries {
+    added: Vec<(Ipv4Addr, String)>,
+}
+
+impl StaticEntries {
+    pub fn new() -> StaticEntries {
+        StaticEntries { added: Vec::new() }
+    }
+
+    // add `ip -> mac` on `device` unless the cache already resolves `ip`,
+    // returns whether an entry was added
+    pub fn ensure(&mut self, ip: Ipv4Addr, mac: MacAddr, device: &str) -> io::Result<bool> {
+        match lookup(&read_arp_table()?, ip) {
+            Some(entry) if entry.mac == mac => return Ok(false),
+            Some(entry) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} is already cached as {}", ip, entry.mac),
+                ))
+            }
+            None => {}
+        }
+        // replace rather than add, so an incomplete entry left by an earlier
+        // failed resolution doesn't make the command fail
+        run_ip_neigh(&[
+            "replace",
+            &ip.to_string(),
+            "lladdr",
+            &format!("{:x}", mac),
+            "dev",
+            device,
+            "nud",
+            "permanent",
+        ])?;
+        self.added.push((ip, device.to_string()));
+        Ok(true)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+    }
+
+    // forget the added entries without removing them, for --keep-arp
+    pub fn keep(&mut self) -> Vec<Ipv4Addr> {
+        self.added.drain(..).map(|(ip, _)| ip).collect()
+    }
+
+    // remove every entry that was added, returning the ones that could not be
+    pub fn cleanup(&mut self) -> Vec<(Ipv4Addr, io::Error)> {
+        self.added
+            .drain(..)
+            .filter_map(|(ip, device)| {
+                run_ip_neigh(&["del", &ip.to_string(), "dev", &device])
+                    .err()
+                    .map(|err| (ip, err))
+            })
+            .collect()
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn run_ip_neigh(args: &[&str]) -> io::Result<()> {
+    let output = Command::new("ip").arg("neigh").args(args).output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn run_ip_neigh(_args: &[&str]) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "adding ARP entries is only supported on Linux",
+    ))
+}
@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+// parse a human duration such as 500ms, 2s, 30m, 2h, 1d or 1h30m
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let mut total = Duration::ZERO;
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return Err("empty duration".to_string());
+    }
+
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if digits == 0 {
+            return Err(format!(
+                "invalid duration {:?}, expected e.g. 500ms, 2s, 30m or 1h30m",
+                s
+            ));
+        }
+        let value: u64 = rest[..digits]
+            .parse()
+            .map_err(|_| format!("duration {:?} is too large", s))?;
+        rest = &rest[digits..];
+
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let unit = match &rest[..unit_len] {
+            "ms" => Duration::from_millis(1),
+            "s" => Duration::from_secs(1),
+            "m" => Duration::from_secs(60),
+            "h" => Duration::from_secs(60 * 60),
+            "d" => Duration::from_secs(24 * 60 * 60),
+            "" => {
+                return Err(format!(
+                    "missing unit in duration {:?}, use ms, s, m, h or d",
+                    s
+                ))
+            }
+            unit => {
+                return Err(format!(
+                    "unknown unit {:?} in duration {:?}, use ms, s, m, h or d",
+                    unit, s
+                ))
+            }
+        };
+        rest = &rest[unit_len..];
+
+        let part = unit
+            .checked_mul(
+                u32::try_from(value).map_err(|_| format!("duration {:?} is too large", s))?,
+            )
+            .ok_or_else(|| format!("duration {:?} is too large", s))?;
+        total = total
+            .checked_add(part)
+            .ok_or_else(|| format!("duration {:?} is too large", s))?;
+    }
+
+    Ok(total)
+}
+
+// like parse_duration, but a bare number is that many milliseconds, for the
+// flags that took milliseconds before they took units
+pub fn parse_millis_or_duration(s: &str) -> Result<Duration, String> {
+    parse_with_default_unit(s, Duration::from_millis(1))
+}
+
+// like parse_duration, but a bare number is that many seconds
+pub fn parse_secs_or_duration(s: &str) -> Result<Duration, String> {
+    parse_with_default_unit(s, Duration::from_secs(1))
+}
+
+fn parse_with_default_unit(s: &str, unit: Duration) -> Result<Duration, String> {
+    let trimmed = s.trim();
+    if trimmed.is_empty() || !trimmed.chars().all(|c| c.is_ascii_digit()) {
+        return parse_duration(s);
+    }
+    let value: u32 = trimmed
+        .parse()
+        .map_err(|_| format!("duration {:?} is too large", s))?;
+    unit.checked_mul(value)
+        .ok_or_else(|| format!("duration {:?} is too large", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bare_numbers_take_the_default_unit() {
+        assert_eq!(
+            parse_millis_or_duration("250"),
+            Ok(Duration::from_millis(250))
+        );
+        assert_eq!(parse_millis_or_duration("2s"), Ok(Duration::from_secs(2)));
+        assert_eq!(parse_secs_or_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_secs_or_duration("1m30s"), Ok(Duration::from_secs(90)));
+        assert!(parse_millis_or_duration("-5").is_err());
+        assert!(parse_secs_or_duration("").is_err());
+        assert!(parse_duration("500").is_err());
+    }
+}
@@ -0,0 +1,68 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::mac::MacParseError;
+
+/// Errors returned while preparing or sending magic packets.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum WakeError {
+    /// A socket or interface operation failed.
+    Io(io::Error),
+    /// The socket may not send broadcasts, usually inside a restricted
+    /// container or sandbox.
+    BroadcastNotPermitted(io::Error),
+    /// A MAC address could not be parsed.
+    MacParse(MacParseError),
+    /// A network address could not be parsed.
+    AddrParse(ipnet::AddrParseError),
+    /// There is no network to broadcast on, e.g. the interface has no IPv4
+    /// address.
+    NoNetworks,
+}
+
+impl fmt::Display for WakeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WakeError::Io(err) => write!(f, "{}", err),
+            WakeError::BroadcastNotPermitted(_) => write!(
+                f,
+                "broadcast not permitted; run with the privileges needed to send broadcasts, or outside the restricted container"
+            ),
+            WakeError::MacParse(err) => write!(f, "{}", err),
+            WakeError::AddrParse(err) => write!(f, "{}", err),
+            WakeError::NoNetworks => write!(f, "no network to broadcast on was found"),
+        }
+    }
+}
+
+impl Error for WakeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WakeError::Io(err) => Some(err),
+            WakeError::BroadcastNotPermitted(err) => Some(err),
+            WakeError::MacParse(err) => Some(err),
+            WakeError::AddrParse(err) => Some(err),
+            WakeError::NoNetworks => None,
+        }
+    }
+}
+
+impl From<io::Error> for WakeError {
+    fn from(err: io::Error) -> WakeError {
+        WakeError::Io(err)
+    }
+}
+
+impl From<MacParseError> for WakeError {
+    fn from(err: MacParseError) -> WakeError {
+        WakeError::MacParse(err)
+    }
+}
+
+impl From<ipnet::AddrParseError> for WakeError {
+    fn from(err: ipnet::AddrParseError) -> WakeError {
+        WakeError::AddrParse(err)
+    }
+}
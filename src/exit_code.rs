@@ -0,0 +1,44 @@
+// the process exit status, so scripts can tell why a run failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    Success = 0,
+    // some sends failed
+    PartialFailure = 1,
+    // every send failed
+    TotalFailure = 2,
+    InvalidArguments = 3,
+    // reading the input file, listing interfaces or opening the socket failed
+    Io = 4,
+    NoNetworks = 5,
+    // Ctrl-C while waiting for --at, while ARP entries are added, between
+    // repeats or retries, while --wake-and-ssh waits for the host, or in
+    // --watch, --server or the keepalive loop, the shell convention for SIGINT
+    Interrupted = 130,
+}
+
+impl ExitCode {
+    // the code for a run where `failed` of `total` sends failed
+    pub fn from_sends(total: usize, failed: usize) -> ExitCode {
+        if failed == 0 {
+            ExitCode::Success
+        } else if failed == total {
+            ExitCode::TotalFailure
+        } else {
+            ExitCode::PartialFailure
+        }
+    }
+
+    // input that was skipped, e.g. an invalid MAC address, turns a success
+    // into a partial failure
+    pub fn with_skipped(self, skipped: usize) -> ExitCode {
+        if self == ExitCode::Success && skipped > 0 {
+            ExitCode::PartialFailure
+        } else {
+            self
+        }
+    }
+
+    pub fn exit(self) -> ! {
+        std::process::exit(self as i32)
+    }
+}
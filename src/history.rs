@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use wol::MacAddr;
+
+// when every MAC address was last woken, kept in a file of
+// "MAC UNIX-SECONDS" lines for --min-interval
+pub struct WakeHistory {
+    path: PathBuf,
+    last_wakes: HashMap<MacAddr, u64>,
+}
+
+// $XDG_STATE_HOME/wol/last-wakes, falling back to ~/.local/state and then
+// the temp directory
+pub fn default_path() -> PathBuf {
+    let state_dir = env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/state")))
+        .unwrap_or_else(env::temp_dir);
+    state_dir.join("wol").join("last-wakes")
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl WakeHistory {
+    pub fn new(path: &Path) -> WakeHistory {
+        WakeHistory {
+            path: path.to_path_buf(),
+            last_wakes: HashMap::new(),
+        }
+    }
+
+    // a missing file is an empty history, lines that can't be parsed are
+    // dropped
+    pub fn load(path: &Path) -> io::Result<WakeHistory> {
+        let content = match fs::read_to_string(path) {
+            Ok(v) => v,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(WakeHistory::new(path)),
+            Err(err) => return Err(err),
+        };
+        let last_wakes = content
+            .lines()
+            .filter_map(|line| {
+                let (mac, seconds) = line.split_once(' ')?;
+                Some((mac.parse().ok()?, seconds.trim().parse().ok()?))
+            })
+            .collect();
+        Ok(WakeHistory {
+            path: path.to_path_buf(),
+            last_wakes,
+        })
+    }
+
+    // how long ago `mac` was woken, if that is less than `window`
+    pub fn woken_within(
+        &self,
+        mac: &MacAddr,
+        window: Duration,
+        now: SystemTime,
+    ) -> Option<Duration> {
+        let last_wake = *self.last_wakes.get(mac)?;
+        let ago = Duration::from_secs(unix_seconds(now).saturating_sub(last_wake));
+        if ago < window {
+            Some(ago)
+        } else {
+            None
+        }
+    }
+
+    pub fn record(&mut self, mac: MacAddr, now: SystemTime) {
+        self.last_wakes.insert(mac, unix_seconds(now));
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut entries: Vec<(&MacAddr, &u64)> = self.last_wakes.iter().collect();
+        entries.sort_by_key(|(_, seconds)| **seconds);
+        let content: String = entries
+            .iter()
+            .map(|(mac, seconds)| format!("{} {}\n", mac, seconds))
+            .collect();
+
+        // write next to the file and rename, so concurrent runs never read a
+        // half written file
+        let mut temp_path = self.path.clone().into_os_string();
+        temp_path.push(".tmp");
+        fs::write(&temp_path, content)?;
+        fs::rename(&temp_path, &self.path)
+    }
+}
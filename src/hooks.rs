@@ -0,0 +1,70 @@
+use std::io;
+use std::process::{Child, Command, Stdio};
+
+// how many hook commands run at the same time
+const MAX_RUNNING: usize = 4;
+
+// a --on-success or --on-failure command for one target
+pub struct Hook {
+    pub command: String,
+    pub env: Vec<(&'static str, String)>,
+    // what the hook is for, used when reporting its failure
+    pub label: String,
+}
+
+// run every hook through the shell, at most MAX_RUNNING at a time, and return
+// the label and reason of every hook that failed
+pub fn run_all(hooks: Vec<Hook>) -> Vec<(String, String)> {
+    let mut failures = Vec::new();
+    let mut running: Vec<(String, Child)> = Vec::new();
+
+    for hook in hooks {
+        if running.len() == MAX_RUNNING {
+            let (label, child) = running.remove(0);
+            if let Err(err) = wait(child) {
+                failures.push((label, err));
+            }
+        }
+        match spawn(&hook) {
+            Ok(child) => running.push((hook.label, child)),
+            Err(err) => failures.push((hook.label, err.to_string())),
+        }
+    }
+    for (label, child) in running {
+        if let Err(err) = wait(child) {
+            failures.push((label, err));
+        }
+    }
+
+    failures
+}
+
+// `command` run through the shell, `sh -c` or `cmd /C` on Windows
+pub fn shell(command: &str) -> Command {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell.arg(command);
+    shell
+}
+
+fn spawn(hook: &Hook) -> io::Result<Child> {
+    shell(&hook.command)
+        .envs(hook.env.iter().map(|(name, value)| (name, value)))
+        .stdin(Stdio::null())
+        .spawn()
+}
+
+fn wait(mut child: Child) -> Result<(), String> {
+    match child.wait() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(status.to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
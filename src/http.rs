@@ -0,0 +1,43 @@
+use std::time::Duration;
+
+// a wake request sent to a router's WOL web API instead of a local broadcast
+pub struct HttpWake {
+    pub endpoint: String,
+    // request body with {mac} substituted, e.g. {"mac":"{mac}"}
+    pub body: String,
+    pub content_type: String,
+    pub headers: Vec<(String, String)>,
+}
+
+// split a "Name: value" header argument
+pub fn parse_header(header: &str) -> Result<(String, String), String> {
+    match header.split_once(':') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(format!(
+            "invalid header {:?}, expected \"Name: value\"",
+            header
+        )),
+    }
+}
+
+impl HttpWake {
+    // POST the wake request for one MAC address, returning the HTTP status
+    pub fn send(&self, mac_address: &str) -> Result<u16, String> {
+        let mut request = ureq::post(&self.endpoint)
+            .timeout(Duration::from_secs(10))
+            .set("Content-Type", &self.content_type);
+        for (name, value) in &self.headers {
+            request = request.set(name, value);
+        }
+
+        match request.send_string(&self.body.replace("{mac}", mac_address)) {
+            Ok(response) => Ok(response.status()),
+            Err(ureq::Error::Status(code, response)) => {
+                Err(format!("HTTP {} {}", code, response.status_text()))
+            }
+            Err(ureq::Error::Transport(err)) => Err(err.to_string()),
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use if_addrs::get_if_addrs;
+use ipnet::Ipv4Net;
+use std::io;
+use std::net::Ipv4Addr;
+
+use crate::error::WakeError;
+
+/// An IPv4 address assigned to a local interface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceAddr {
+    pub name: String,
+    pub ip: Ipv4Addr,
+    pub netmask: Ipv4Addr,
+    /// Whether the interface is up and running, i.e. has a carrier.
+    pub up: bool,
+}
+
+impl InterfaceAddr {
+    /// The network the address belongs to, e.g. `192.168.1.10/24`.
+    ///
+    /// The prefix is the run of leading one bits of the netmask, so a
+    /// malformed mask with stray bits after the first zero is treated as
+    /// the contiguous mask in front of them.
+    pub fn network(&self) -> Ipv4Net {
+        let prefix_len = u32::from(self.netmask).leading_ones() as u8;
+        Ipv4Net::new(self.ip, prefix_len).unwrap()
+    }
+
+    /// Whether a magic packet broadcast here can reach a device: the
+    /// interface is up, and the address is neither loopback nor link-local.
+    pub fn is_broadcast_candidate(&self) -> bool {
+        self.up && !self.ip.is_loopback() && !self.ip.is_link_local()
+    }
+}
+
+/// A source of local interface addresses.
+///
+/// [`SystemInterfaces`] asks the operating system, other implementations can
+/// feed fixed data, e.g. in tests.
+pub trait InterfaceProvider {
+    fn interfaces(&self) -> io::Result<Vec<InterfaceAddr>>;
+}
+
+/// The interfaces reported by the operating system.
+pub struct SystemInterfaces;
+
+impl InterfaceProvider for SystemInterfaces {
+    fn interfaces(&self) -> io::Result<Vec<InterfaceAddr>> {
+        let if_addrs = get_if_addrs()?;
+        let mut addrs = Vec::new();
+
+        for if_addr in if_addrs {
+            if let if_addrs::IfAddr::V4(if_v4_addr) = if_addr.addr {
+                addrs.push(InterfaceAddr {
+                    name: if_addr.name,
+                    ip: if_v4_addr.ip,
+                    netmask: if_v4_addr.netmask,
+                    up: if_addr.oper_status == if_addrs::IfOperStatus::Up,
+                });
+            }
+        }
+
+        Ok(addrs)
+    }
+}
+
+/// Returns the IPv4 network of every address on the local interfaces that
+/// is a [broadcast candidate](InterfaceAddr::is_broadcast_candidate).
+pub fn get_local_ip_nets() -> Result<Vec<Ipv4Net>, WakeError> {
+    get_local_ip_nets_from(&SystemInterfaces)
+}
+
+/// Returns the IPv4 network of every broadcast candidate reported by
+/// `provider`, sorted by address and then prefix length, so the order
+/// doesn't depend on how the operating system lists its interfaces.
+pub fn get_local_ip_nets_from(
+    provider: &impl InterfaceProvider,
+) -> Result<Vec<Ipv4Net>, WakeError> {
+    let mut ip_nets: Vec<Ipv4Net> = provider
+        .interfaces()?
+        .iter()
+        .filter(|if_addr| if_addr.is_broadcast_candidate())
+        .map(InterfaceAddr::network)
+        .collect();
+    ip_nets.sort();
+    Ok(ip_nets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedInterfaces(Vec<InterfaceAddr>);
+
+    impl InterfaceProvider for FixedInterfaces {
+        fn interfaces(&self) -> io::Result<Vec<InterfaceAddr>> {
+            Ok(self.0.clone())
+        }
+    }
+
+    struct FailingInterfaces;
+
+    impl InterfaceProvider for FailingInterfaces {
+        fn interfaces(&self) -> io::Result<Vec<InterfaceAddr>> {
+            Err(io::Error::other("no interfaces"))
+        }
+    }
+
+    fn addr(name: &str, ip: [u8; 4], netmask: [u8; 4]) -> InterfaceAddr {
+        InterfaceAddr {
+            name: name.to_string(),
+            ip: Ipv4Addr::from(ip),
+            netmask: Ipv4Addr::from(netmask),
+            up: true,
+        }
+    }
+
+    #[test]
+    fn computes_a_network_per_address() {
+        let provider = FixedInterfaces(vec![
+            addr("eth0", [192, 168, 1, 10], [255, 255, 255, 0]),
+            addr("eth1", [172, 16, 12, 34], [255, 255, 0, 0]),
+            addr("ptp0", [10, 0, 0, 0], [255, 255, 255, 254]),
+        ]);
+
+        let ip_nets = get_local_ip_nets_from(&provider).unwrap();
+
+        assert_eq!(
+            ip_nets,
+            vec![
+                "10.0.0.0/31".parse::<Ipv4Net>().unwrap(),
+                "172.16.12.34/16".parse().unwrap(),
+                "192.168.1.10/24".parse().unwrap(),
+            ]
+        );
+        assert_eq!(ip_nets[0].broadcast(), Ipv4Addr::new(10, 0, 0, 1));
+        assert_eq!(ip_nets[1].broadcast(), Ipv4Addr::new(172, 16, 255, 255));
+        assert_eq!(ip_nets[2].broadcast(), Ipv4Addr::new(192, 168, 1, 255));
+    }
+
+    #[test]
+    fn skips_down_loopback_and_link_local_addresses() {
+        let mut down = addr("eth1", [10, 1, 0, 5], [255, 255, 255, 0]);
+        down.up = false;
+        let provider = FixedInterfaces(vec![
+            addr("lo", [127, 0, 0, 1], [255, 0, 0, 0]),
+            addr("eth0", [192, 168, 1, 10], [255, 255, 255, 0]),
+            addr("eth0", [169, 254, 12, 34], [255, 255, 0, 0]),
+            down,
+        ]);
+
+        let ip_nets = get_local_ip_nets_from(&provider).unwrap();
+
+        assert_eq!(ip_nets, ["192.168.1.10/24".parse::<Ipv4Net>().unwrap()]);
+    }
+
+    #[test]
+    fn computes_prefix_and_broadcast_for_odd_masks() {
+        let cases = [
+            (
+                [192, 168, 1, 10],
+                [255, 255, 255, 0],
+                24,
+                [192, 168, 1, 255],
+            ),
+            ([172, 16, 4, 1], [255, 255, 0, 0], 16, [172, 16, 255, 255]),
+            (
+                [192, 168, 1, 200],
+                [255, 255, 255, 128],
+                25,
+                [192, 168, 1, 255],
+            ),
+            (
+                [192, 168, 1, 100],
+                [255, 255, 255, 128],
+                25,
+                [192, 168, 1, 127],
+            ),
+            ([10, 0, 0, 5], [255, 255, 255, 252], 30, [10, 0, 0, 7]),
+            ([10, 0, 0, 6], [255, 255, 255, 254], 31, [10, 0, 0, 7]),
+            ([10, 0, 0, 6], [255, 255, 255, 255], 32, [10, 0, 0, 6]),
+            // stray bits after the first zero are ignored
+            ([10, 1, 2, 3], [255, 255, 0, 255], 16, [10, 1, 255, 255]),
+        ];
+
+        for (ip, netmask, prefix_len, broadcast) in cases {
+            let network = addr("eth0", ip, netmask).network();
+            assert_eq!(network.prefix_len(), prefix_len, "{:?}", netmask);
+            assert_eq!(
+                network.broadcast(),
+                Ipv4Addr::from(broadcast),
+                "{:?}",
+                netmask
+            );
+        }
+    }
+
+    #[test]
+    fn no_interfaces_gives_no_networks() {
+        let ip_nets = get_local_ip_nets_from(&FixedInterfaces(Vec::new())).unwrap();
+
+        assert!(ip_nets.is_empty());
+    }
+
+    #[test]
+    fn provider_errors_are_returned() {
+        let err = get_local_ip_nets_from(&FailingInterfaces).unwrap_err();
+
+        assert!(matches!(err, WakeError::Io(_)));
+    }
+}
@@ -0,0 +1,207 @@
+use std::net::Ipv4Addr;
+
+use wol::{MacAddr, Target};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeaseFormat {
+    // dnsmasq.leases: one "EXPIRY MAC IP HOSTNAME CLIENT-ID" line per lease
+    Dnsmasq,
+    // dhcpd.leases of ISC dhcpd: "lease IP { ... }" blocks
+    Isc,
+}
+
+// the targets of every lease with their MAC address as it was written, and a
+// message for every lease that was skipped
+pub fn parse_leases(content: &str, format: LeaseFormat) -> (Vec<(Target, String)>, Vec<String>) {
+    match format {
+        LeaseFormat::Dnsmasq => parse_dnsmasq(content),
+        LeaseFormat::Isc => parse_isc(content),
+    }
+}
+
+// the lease file format `content` is in, None when it doesn't look like a
+// lease file. dhcpd.leases has "lease IP {" blocks after its header lines,
+// dnsmasq.leases starts with an "EXPIRY MAC IP" line, or a DUID line
+pub fn detect(content: &str) -> Option<LeaseFormat> {
+    let lines = content.lines().map(str::trim);
+    if lines
+        .clone()
+        .any(|line| line.starts_with("lease ") && line.ends_with('{'))
+    {
+        return Some(LeaseFormat::Isc);
+    }
+    let first = lines
+        .filter(|line| !line.is_empty() && !line.starts_with("duid "))
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .next()?;
+    let is_dnsmasq = first.len() >= 4
+        && first[0].parse::<u64>().is_ok()
+        && first[1].parse::<MacAddr>().is_ok()
+        && first[2].parse::<Ipv4Addr>().is_ok();
+    is_dnsmasq.then_some(LeaseFormat::Dnsmasq)
+}
+
+fn parse_dnsmasq(content: &str) -> (Vec<(Target, String)>, Vec<String>) {
+    let mut targets = Vec::new();
+    let mut skipped = Vec::new();
+
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        // a DUID line, or an empty one
+        if fields.len() < 4 {
+            continue;
+        }
+        match lease_target(fields[1], fields[2], Some(fields[3])) {
+            Ok(target) => targets.push((target, fields[1].to_string())),
+            Err(err) => skipped.push(err),
+        }
+    }
+
+    (targets, skipped)
+}
+
+// a "lease IP { ... }" block of dhcpd.leases
+struct IscLease {
+    ip: String,
+    mac: Option<String>,
+    hostname: Option<String>,
+    active: bool,
+}
+
+// dhcpd appends a new block every time a lease changes, so only the last
+// block of every address counts
+fn parse_isc(content: &str) -> (Vec<(Target, String)>, Vec<String>) {
+    let mut blocks: Vec<IscLease> = Vec::new();
+    let mut current: Option<IscLease> = None;
+
+    for line in content.lines() {
+        let line = line.trim().trim_end_matches(';');
+        if let Some(rest) = line.strip_prefix("lease ") {
+            let ip = rest.trim_end_matches('{').trim().to_string();
+            current = Some(IscLease {
+                ip,
+                mac: None,
+                hostname: None,
+                active: true,
+            });
+        } else if line == "}" {
+            if let Some(block) = current.take() {
+                blocks.retain(|lease| lease.ip != block.ip);
+                blocks.push(block);
+            }
+        } else if let Some(lease) = current.as_mut() {
+            if let Some(value) = line.strip_prefix("hardware ethernet ") {
+                lease.mac = Some(value.to_string());
+            } else if let Some(value) = line.strip_prefix("client-hostname ") {
+                lease.hostname = Some(value.trim_matches('"').to_string());
+            } else if let Some(value) = line.strip_prefix("binding state ") {
+                lease.active = value == "active";
+            }
+        }
+    }
+
+    let mut targets = Vec::new();
+    let mut skipped = Vec::new();
+    for lease in blocks {
+        if !lease.active {
+            continue;
+        }
+        let mac = match &lease.mac {
+            Some(v) => v,
+            None => {
+                skipped.push(format!("lease {} has no hardware address", lease.ip));
+                continue;
+            }
+        };
+        match lease_target(mac, &lease.ip, lease.hostname.as_deref()) {
+            Ok(target) => targets.push((target, mac.clone())),
+            Err(err) => skipped.push(err),
+        }
+    }
+
+    (targets, skipped)
+}
+
+fn lease_target(mac: &str, ip: &str, hostname: Option<&str>) -> Result<Target, String> {
+    let mac_address = mac
+        .parse::<MacAddr>()
+        .map_err(|err| format!("lease {}: {}, original MAC address: {}", ip, err, mac))?;
+    let ip_address = ip
+        .parse::<Ipv4Addr>()
+        .map_err(|_| format!("lease for {}: invalid IP address {}", mac, ip))?;
+    // dnsmasq writes * for clients that sent no hostname
+    let name = hostname.filter(|name| !name.is_empty() && *name != "*");
+
+    Ok(Target {
+        mac: mac_address,
+        ip: Some(ip_address),
+        name: name.map(str::to_string),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_dnsmasq_leases() {
+        let content = "\
+1700000000 00:11:22:33:44:55 192.168.1.20 nas 01:00:11:22:33:44:55
+1700000000 66:77:88:99:aa:bb 192.168.1.21 * *
+duid 00:01:00:01:2c:00:00:00:00:11:22:33:44:55
+1700000000 zz 192.168.1.22 broken *
+";
+        let (targets, skipped) = parse_leases(content, LeaseFormat::Dnsmasq);
+
+        assert_eq!(targets.len(), 2);
+        assert_eq!(
+            targets[0].0.mac,
+            MacAddr::new([0x00, 0x11, 0x22, 0x33, 0x44, 0x55])
+        );
+        assert_eq!(targets[0].0.ip, Some(Ipv4Addr::new(192, 168, 1, 20)));
+        assert_eq!(targets[0].0.name.as_deref(), Some("nas"));
+        assert_eq!(targets[1].0.name, None);
+        assert_eq!(targets[1].1, "66:77:88:99:aa:bb");
+        assert_eq!(skipped.len(), 1);
+    }
+
+    #[test]
+    fn parses_isc_leases_keeping_the_last_block() {
+        let content = r#"
+# The format of this file is documented in the dhcpd.leases(5) manual page.
+lease 192.168.1.20 {
+  starts 4 2024/05/02 07:00:00;
+  binding state active;
+  hardware ethernet 00:11:22:33:44:55;
+  client-hostname "old";
+}
+lease 192.168.1.21 {
+  binding state free;
+  hardware ethernet 66:77:88:99:aa:bb;
+}
+lease 192.168.1.20 {
+  binding state active;
+  hardware ethernet 00:11:22:33:44:55;
+  client-hostname "nas";
+}
+"#;
+        let (targets, skipped) = parse_leases(content, LeaseFormat::Isc);
+
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].0.ip, Some(Ipv4Addr::new(192, 168, 1, 20)));
+        assert_eq!(targets[0].0.name.as_deref(), Some("nas"));
+        assert!(skipped.is_empty());
+    }
+
+    #[test]
+    fn detects_the_lease_format() {
+        let dnsmasq = "duid 00:01:00:01\n1700000000 00:11:22:33:44:55 192.168.1.20 nas *\n";
+        let isc = "authoring-byte-order little-endian;\nlease 192.168.1.20 {\n}\n";
+        let plain = "# lab\n00:11:22:33:44:55 192.168.1.20\n001122334455\n";
+
+        assert_eq!(detect(dnsmasq), Some(LeaseFormat::Dnsmasq));
+        assert_eq!(detect(isc), Some(LeaseFormat::Isc));
+        assert_eq!(detect(plain), None);
+        assert_eq!(detect(""), None);
+    }
+}
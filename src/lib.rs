@@ -0,0 +1,64 @@
+//! Build and send Wake-on-LAN magic packets.
+//!
+//! It parses MAC addresses, builds magic packets and broadcasts them on
+//! IPv4 networks. [`Wol::builder`] sets up a sender once for many wakes, and
+//! [`wake_all`] wakes a list of targets with progress events. A sender with
+//! its own sockets, like the `wol` command line tool with its batching,
+//! makes the same sends in the same order with [`send_steps`] and
+//! [`record_send`]. With the `tokio` feature,
+//! `tokio::wake` and `tokio::wake_all` do the same without blocking an async
+//! runtime.
+//!
+//! Without the default `std` feature the crate is `no_std` and only
+//! [`MacAddr`] and [`write_magic_packet`] are left, which write the packet
+//! into a caller supplied buffer without allocating, e.g. in firmware, and
+//! the constants of the packet format such as [`WOL_PORT`].
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+mod error;
+#[cfg(feature = "std")]
+mod interfaces;
+mod mac;
+#[cfg(feature = "std")]
+mod options;
+mod packet;
+#[cfg(feature = "std")]
+mod send;
+#[cfg(feature = "std")]
+mod sender;
+#[cfg(feature = "std")]
+mod target;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+#[cfg(feature = "std")]
+mod wake;
+
+#[cfg(feature = "std")]
+pub use error::WakeError;
+#[cfg(feature = "std")]
+pub use interfaces::{
+    get_local_ip_nets, get_local_ip_nets_from, InterfaceAddr, InterfaceProvider, SystemInterfaces,
+};
+pub use mac::{MacAddr, MacParseError};
+#[cfg(feature = "std")]
+pub use options::{OptionsError, WakeOptions, WakeOptionsBuilder};
+#[cfg(feature = "std")]
+pub use packet::{build_magic_packet, build_magic_packet_with_password};
+pub use packet::{
+    write_magic_packet, write_magic_packet_with_password, BufferTooSmall, MAC_REPETITIONS,
+    MAGIC_HEADER, MAGIC_PACKET_LEN, WOL_PORT,
+};
+#[cfg(feature = "std")]
+pub use send::{
+    bind_socket, bind_socket_to_device, bind_unicast_socket, send_magic_packet,
+    send_magic_packet_to, send_network, send_network_to, send_packet, send_wol_packet,
+    NetworkResult,
+};
+#[cfg(feature = "std")]
+pub use sender::{WakeReport, WakeSummary, Wol, WolBuilder};
+#[cfg(feature = "std")]
+pub use target::Target;
+#[cfg(feature = "std")]
+pub use wake::{record_send, send_steps, wake_all, SendStep, WakeEvent};
@@ -0,0 +1,165 @@
+use core::fmt;
+use core::str::FromStr;
+
+/// A MAC address.
+///
+/// Parsing accepts `00:11:22:33:44:55`, `00-11-22-33-44-55`,
+/// `0011.2233.4455` and `001122334455`, with hex digits in either case.
+/// `Display` prints the uppercase colon form, `{:x}` the lowercase one.
+/// With the `serde` feature it is (de)serialized as that string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MacAddr([u8; 6]);
+
+impl MacAddr {
+    pub fn new(bytes: [u8; 6]) -> MacAddr {
+        MacAddr(bytes)
+    }
+
+    pub fn octets(&self) -> [u8; 6] {
+        self.0
+    }
+
+    /// The first three bytes, the OUI that identifies the vendor of the
+    /// network interface.
+    pub fn oui(&self) -> [u8; 3] {
+        [self.0[0], self.0[1], self.0[2]]
+    }
+}
+
+impl From<[u8; 6]> for MacAddr {
+    fn from(bytes: [u8; 6]) -> MacAddr {
+        MacAddr(bytes)
+    }
+}
+
+/// Why a string is not a valid MAC address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MacParseError {
+    /// The string has none of the accepted lengths.
+    Length(usize),
+    /// The separators are missing or in the wrong place.
+    Format,
+    /// Both `:` and `-` separate the bytes, e.g. `00:11-22:33-44:55`.
+    MixedSeparators,
+    /// A byte is not made of two hex digits.
+    Hex([char; 2]),
+}
+
+impl fmt::Display for MacParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MacParseError::Length(len) => write!(
+                f,
+                "invalid MAC address length {} (should be 17, 14 or 12)",
+                len
+            ),
+            MacParseError::Format => write!(
+                f,
+                "invalid MAC address format (should be separated by : or -, dotted, or 12 hex digits)"
+            ),
+            MacParseError::MixedSeparators => write!(
+                f,
+                "invalid MAC address format, it mixes : and - separators (should use one of them throughout)"
+            ),
+            MacParseError::Hex([a, b]) => {
+                write!(f, "invalid MAC address, \"{}{}\" is not hex", a, b)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MacParseError {}
+
+impl FromStr for MacAddr {
+    type Err = MacParseError;
+
+    fn from_str(s: &str) -> Result<MacAddr, MacParseError> {
+        // where the separators go for every accepted length, everything
+        // else is a hex digit
+        let input = s.as_bytes();
+        let (separator, is_separator): (Option<u8>, fn(usize) -> bool) = match input.len() {
+            17 if input[2] == b':' || input[2] == b'-' => (Some(input[2]), |i| i % 3 == 2),
+            17 => return Err(MacParseError::Format),
+            14 => (Some(b'.'), |i| i % 5 == 4),
+            12 => (None, |_| false),
+            len => return Err(MacParseError::Length(len)),
+        };
+
+        // collect the hex digits of the address, checking the separators
+        // are where the format expects them
+        let mut digits = [0u8; 12];
+        let mut count = 0;
+        for (i, &byte) in input.iter().enumerate() {
+            if is_separator(i) {
+                if Some(byte) != separator && separator != Some(b'.') && b":-".contains(&byte) {
+                    return Err(MacParseError::MixedSeparators);
+                }
+                if Some(byte) != separator {
+                    return Err(MacParseError::Format);
+                }
+            } else if Some(byte) == separator || !byte.is_ascii() {
+                return Err(MacParseError::Format);
+            } else {
+                digits[count] = byte;
+                count += 1;
+            }
+        }
+
+        let mut bytes = [0u8; 6];
+        for (byte, pair) in bytes.iter_mut().zip(digits.chunks_exact(2)) {
+            let [high, low] = [pair[0], pair[1]];
+            // hex parsing is case-insensitive, so no normalization is needed
+            *byte = match ((high as char).to_digit(16), (low as char).to_digit(16)) {
+                (Some(high), Some(low)) => (high * 16 + low) as u8,
+                _ => return Err(MacParseError::Hex([high as char, low as char])),
+            };
+        }
+
+        Ok(MacAddr(bytes))
+    }
+}
+
+// formats as uppercase, e.g. 00:1A:2B:3C:4D:5E
+impl fmt::Display for MacAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::UpperHex::fmt(self, f)
+    }
+}
+
+impl fmt::UpperHex for MacAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(
+            f,
+            "{:02X}:{:02X}:{:02X}:{:02X}:{:02X}:{:02X}",
+            a, b, c, d, e, g
+        )
+    }
+}
+
+impl fmt::LowerHex for MacAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(
+            f,
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            a, b, c, d, e, g
+        )
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for MacAddr {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for MacAddr {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<MacAddr, D::Error> {
+        let s = <std::borrow::Cow<str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
@@ -1,231 +1,4199 @@
+mod arp;
+mod duration;
+mod exit_code;
+mod history;
+mod hooks;
+#[cfg(feature = "http")]
+mod http;
+mod leases;
+#[cfg(feature = "mdns")]
+mod mdns;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+mod pcap;
+mod ping;
+mod repeat;
+mod rng;
+mod schedule;
+mod server;
+mod signal;
+mod template;
+mod watch;
+
+use chrono::{DateTime, Local};
 use clap::{Arg, Command};
-use if_addrs::get_if_addrs;
 use ipnet::Ipv4Net;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
-use std::path::PathBuf;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, TcpStream, ToSocketAddrs, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use wol::{
+    bind_socket, bind_socket_to_device, bind_unicast_socket, build_magic_packet,
+    build_magic_packet_with_password, record_send, send_packet, send_steps, InterfaceAddr,
+    InterfaceProvider, MacAddr, NetworkResult, SendStep, SystemInterfaces, Target, WakeError,
+    WakeEvent, MAGIC_HEADER, WOL_PORT,
+};
+
+use exit_code::ExitCode;
+use rng::Rng;
+use template::{Template, TemplateValues};
 
-const WOL_PORT: u16 = 9;
-const MAGIC_HEADER: [u8; 6] = [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+// how long a --retry-on-down probe waits for the TCP connection
+const PROBE_TIMEOUT: Duration = Duration::from_secs(1);
 
 fn main() {
-    let matches = Command::new("Wake on LAN")
-        .version("1.0")
-        .author("sxul07 <sxul07@hotmail.com>")
-        .about("Wake up devices on the network")
-        .arg(
-            Arg::new("mac_address")
-                .value_name("MAC_ADDRESS")
-                .help("Target MAC address, e.g. 00:11:22:33:44:55")
-                .num_args(0..)
-                .required_unless_present("file"), 
-        )
-        .arg(
-            Arg::new("file")
-                .short('f')
-                .long("file")
-                .value_name("FILE")
-                .value_parser(clap::value_parser!(PathBuf))
-                .help("Reads target MAC addresses from a file, one per line. If this option is used, the mac_address option is ignored. Lines starting with # or // are ignored."),
-        )
-        .arg(
-            Arg::new("net")
-                .short('n')
-                .long("net")
-                .value_name("NET")
-                .num_args(0..)
-                .action(clap::ArgAction::Append)
-                .help("Specify the network address to send the broadcast, use CIDR notation, e.g. 192.168.1.0/24"),
-        )
-        .arg(
-            Arg::new("verbose")
-                .short('v')
-                .long("verbose")
-                .help("Enables verbose mode")
-                .action(clap::ArgAction::SetTrue)
-        )
-        .get_matches();
+    let start_time = Instant::now();
+    let matches = cli().try_get_matches().unwrap_or_else(|err| {
+        let _ = err.print();
+        // --help is reported as an error by clap too
+        if err.use_stderr() {
+            ExitCode::InvalidArguments.exit();
+        }
+        ExitCode::Success.exit();
+    });
+
+    if matches.get_flag("version") {
+        if matches.get_flag("json") {
+            println!("{}", version_json());
+        } else {
+            print!("{}", cli().render_version());
+        }
+        ExitCode::Success.exit();
+    }
 
     let verbose_mode = matches.get_flag("verbose");
+    let mac_case = read_mac_case(&matches);
+
+    if let Some(file_path) = matches.get_one::<PathBuf>("normalize_file") {
+        normalize_file(file_path, mac_case).exit();
+    }
+
+    if matches.get_flag("check_config") {
+        check_config(&matches).exit();
+    }
+
+    if matches.get_flag("client") {
+        send_to_server(&matches).exit();
+    }
 
-    let mac_addresses = if let Some(file_path) = matches.get_one::<PathBuf>("file") {
+    if matches.get_flag("resolve_only") {
+        resolve_only(&matches, mac_case).exit();
+    }
+
+    if let Some(input_path) = matches.get_one::<PathBuf>("validate_only_unknown") {
+        unknown_macs(&matches, input_path, mac_case).exit();
+    }
+
+    if matches.get_flag("mdns_discover") {
+        let timeout = *matches.get_one::<Duration>("discover_timeout").unwrap();
+        discover_devices(timeout, mac_case).exit();
+    }
+
+    let fail_fast = matches.get_flag("fail_fast");
+    let allow_exec = matches.get_flag("allow_exec");
+    let mut invalid_macs = 0;
+    let mut keepalives = Vec::new();
+    let mut packets = HashMap::new();
+    let mut mac_texts = MacTexts::new();
+    let mut targets: Vec<Target> = if let Some(file_path) = matches.get_one::<PathBuf>("file") {
         // if file not exist or is not file, it will return with error code
         if !file_path.exists() || !file_path.is_file() {
             println!(
                 "Error: file not exist or is not file, input file path: {:?}",
                 file_path
             );
-            std::process::exit(1);
+            ExitCode::Io.exit();
         }
 
-        read_mac_addresses_from_file(file_path)
+        if matches.get_one::<String>("file_format").unwrap() == "hex-lines" {
+            let (targets, file_packets, skipped) = read_packet_lines(file_path, fail_fast);
+            packets = file_packets;
+            invalid_macs += skipped;
+            targets
+        } else {
+            match file_format(&matches, file_path, verbose_mode) {
+                Some(format) => {
+                    let (targets, skipped) =
+                        read_leases(file_path, format, fail_fast, &mut mac_texts);
+                    invalid_macs += skipped;
+                    targets
+                }
+                None => {
+                    let (targets, file_keepalives, skipped) =
+                        read_targets_from_file(file_path, fail_fast, allow_exec, &mut mac_texts);
+                    keepalives = file_keepalives;
+                    invalid_macs += skipped;
+                    targets
+                }
+            }
+        }
+    } else if let Some(lease_path) = matches.get_one::<PathBuf>("from_dhcp_leases") {
+        let format = match matches.get_one::<String>("lease_format").unwrap().as_str() {
+            "isc" => leases::LeaseFormat::Isc,
+            _ => leases::LeaseFormat::Dnsmasq,
+        };
+        let (targets, skipped) = read_leases(lease_path, format, fail_fast, &mut mac_texts);
+        invalid_macs += skipped;
+        targets
     } else {
-        matches.get_many::<String>("mac_address")
-        .unwrap()
-        .map(|s| s.to_string())
-        .collect()
+        Vec::new()
     };
 
-    let networks = if let Some(custom_net) = matches.get_many::<String>("net") {
-        custom_net
-            .into_iter()
-            .map(|net| {
-                match net.parse::<Ipv4Net>() {
-                    Ok(v) => v,
-                    Err(err) => {
-                        println!(
-                            "Error: {}. Correct address in CIDR notation, e.g. 192.168.1.0/24",
-                            err
-                        );
-                        std::process::exit(1);
+    // MAC addresses on the command line are added to the --file or
+    // --from-dhcp-leases targets unless --file-only, one that is already in
+    // the list is left out
+    if !matches.get_flag("file_only") {
+        for mac_address in matches.get_many::<String>("mac_address").unwrap_or_default() {
+            match parse_mac_range(mac_address) {
+                Ok(v) => {
+                    let texts = written_macs(mac_address, &v);
+                    for (mac, text) in v.into_iter().zip(texts) {
+                        if !targets.iter().any(|target| target.mac == mac) {
+                            targets.push(Target::new(mac));
+                            mac_texts.entry(mac).or_insert(text);
+                        }
                     }
                 }
-            })
-            .collect()
-    } else {
-        get_local_ip_nets()
-    };
+                Err(err) => {
+                    println!("Error: {}, original MAC address: {}", err, mac_address);
+                    if fail_fast {
+                        ExitCode::InvalidArguments.exit();
+                    }
+                    invalid_macs += 1;
+                }
+            }
+        }
+    }
+
+    if targets.is_empty() && invalid_macs > 0 {
+        ExitCode::InvalidArguments.exit();
+    }
+    let mac_case = mac_case.with_texts(&mac_texts);
 
-    for mac_address in &mac_addresses {
-        // check the mac address format
-        if mac_address.len() != 17 {
+    // --oui-prefix keeps only the devices of some vendors
+    if let Some(prefixes) = matches.get_many::<String>("oui_prefix") {
+        let ouis: Vec<[u8; 3]> = prefixes
+            .map(|prefix| {
+                parse_oui(prefix).unwrap_or_else(|err| {
+                    println!("Error: {}", err);
+                    ExitCode::InvalidArguments.exit();
+                })
+            })
+            .collect();
+        let total = targets.len();
+        targets.retain(|target| ouis.contains(&target.mac.oui()));
+        keepalives.retain(|(target, _)| targets.contains(target));
+        if targets.is_empty() {
+            println!("Error: --oui-prefix matched none of {}", plural(total, "target"));
+            ExitCode::InvalidArguments.exit();
+        }
+        if !matches.get_flag("compact") && targets.len() < total {
             println!(
-                "Error: invalid MAC address length (should be 17), original MAC address: {}",
-                mac_address
+                "Only using {} of {} targets, --oui-prefix left out {}",
+                targets.len(),
+                total,
+                total - targets.len()
             );
-            continue;
         }
-        if !mac_address.contains(':') && !mac_address.contains('-') {
+    }
+
+    if matches.get_flag("list_vendors") {
+        list_vendors(&targets, mac_case);
+        ExitCode::Success.with_skipped(invalid_macs).exit();
+    }
+
+    if matches.get_flag("randomize_order") {
+        let mut rng = match matches.get_one::<u64>("seed") {
+            Some(seed) => Rng::new(*seed),
+            None => Rng::from_time(),
+        };
+        rng.shuffle(&mut targets);
+    }
+
+    let mut truncated = 0;
+    if let Some(count) = matches.get_one::<u64>("count").map(|count| *count as usize) {
+        if targets.len() > count {
+            truncated = targets.len() - count;
+            if !matches.get_flag("compact") {
+                println!(
+                    "Only using the first {} of {} targets, --count left out {}",
+                    count,
+                    targets.len(),
+                    truncated
+                );
+            }
+            targets.truncate(count);
+            keepalives.retain(|(target, _)| targets.contains(target));
+        }
+    }
+
+    if matches.get_flag("confirm_each") && !matches.get_flag("yes") && !io::stdin().is_terminal() {
+        println!("Error: --confirm-each asks on the terminal, add --yes to wake every target");
+        ExitCode::InvalidArguments.exit();
+    }
+
+    if matches.get_flag("echo_targets") {
+        for target in &targets {
+            let keepalive = keepalives
+                .iter()
+                .find(|(keepalive_target, _)| keepalive_target == target)
+                .map(|(_, interval)| format!("  keepalive={:?}", interval))
+                .unwrap_or_default();
             println!(
-                "Error: invalid MAC address format (should be separated by : or -), original MAC address: {}",
-                mac_address
+                "{:<17}  {:<15}  {}{}",
+                format_target_mac(target, mac_case),
+                target.ip.map(|ip| ip.to_string()).unwrap_or("-".to_string()),
+                target.name.as_deref().unwrap_or("-"),
+                keepalive
             );
-            continue;
         }
+        ExitCode::Success.with_skipped(invalid_macs).exit();
+    }
 
-        // uppercase the mac address
-        let mac_address = mac_address.to_uppercase();
+    // what is sent, which needs no network
+    let password = read_password(&matches);
+    let raw_payload = matches
+        .get_one::<PathBuf>("raw_payload")
+        .map(|path| read_raw_payload(path));
+    let payload_prefix = matches.get_one::<String>("payload_prefix").map(|prefix| {
+        parse_hex(prefix).unwrap_or_else(|err| {
+            println!("Error: {} in --payload-prefix", err);
+            ExitCode::InvalidArguments.exit();
+        })
+    });
+    if matches.get_flag("dry_run_packet_only") {
+        for target in &targets {
+            let packet = packets.get(&target.mac).cloned().unwrap_or_else(|| {
+                build_packet(
+                    &target.mac,
+                    password.as_deref(),
+                    raw_payload.as_deref(),
+                    payload_prefix.as_deref(),
+                )
+            });
+            println!("{}  {}", format_target_mac(target, mac_case), hex(&packet));
+        }
+        ExitCode::Success.with_skipped(invalid_macs).exit();
+    }
 
-        send_wol_packet(&mac_address, &networks, verbose_mode);
+    if let Some(wake_time) = matches.get_one::<DateTime<Local>>("at") {
+        println!("Waking at {}", wake_time.format("%Y-%m-%d %H:%M:%S"));
+        signal::install();
+        if !schedule::wait_until(*wake_time) {
+            println!("Interrupted, no magic packets were sent");
+            ExitCode::Interrupted.exit();
+        }
     }
-}
 
-fn send_wol_packet(mac_address: &str, networks: &Vec<Ipv4Net>, verbose_mode: bool) {
-    let socket =
-        UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 0)).unwrap();
-    for broadcast_net in networks {
-        match send_magic_packet(&socket, mac_address, &broadcast_net) {
-            Ok(_) => {}
-            Err(err) => {
-                println!("Error: {}, original MAC address: {}", err, mac_address);
-                break;
-            }
+    if let Some(endpoint) = matches.get_one::<String>("http_endpoint") {
+        if matches.get_flag("explain") {
+            println!(
+                "Will POST a wake request for {} to {}.",
+                plural(targets.len(), "device"),
+                endpoint
+            );
         }
-        if verbose_mode {
+        if matches.get_flag("dry_run") {
+            ExitCode::Success.with_skipped(invalid_macs).exit();
+        }
+        wake_over_http(endpoint, &matches, &targets, mac_case, verbose_mode)
+            .with_skipped(invalid_macs)
+            .exit();
+    }
+
+    if let Some(broker) = matches.get_one::<String>("mqtt_broker") {
+        let topic = matches.get_one::<String>("mqtt_topic").unwrap();
+        if matches.get_flag("explain") {
             println!(
-                "Sent magic packet to {}, and broadcasted on {}",
-                mac_address, broadcast_net
+                "Will publish a wake message for {} on {} at {}.",
+                plural(targets.len(), "device"),
+                topic,
+                broker
             );
         }
+        if matches.get_flag("dry_run") {
+            ExitCode::Success.with_skipped(invalid_macs).exit();
+        }
+        wake_over_mqtt(broker, &matches, &targets, mac_case, verbose_mode)
+            .with_skipped(invalid_macs)
+            .exit();
+    }
+
+    let host_ip = matches.get_one::<String>("host").map(|host| match resolve_host(host) {
+        Ok(v) => v,
+        Err(err) => {
+            println!("Error: failed to resolve host {}: {}", host, err);
+            ExitCode::InvalidArguments.exit();
+        }
+    });
+
+    // networks are only needed when some target is broadcast to, which any
+    // --server client can ask for
+    let server = matches.get_flag("server");
+    let needs_broadcast =
+        host_ip.is_none() && (server || targets.iter().any(|target| target.ip.is_none()));
+    let no_broadcast = matches.get_flag("no_broadcast_fallback");
+    if no_broadcast && needs_broadcast {
+        let without_ip = targets.iter().filter(|target| target.ip.is_none()).count();
+        println!(
+            "Error: --no-broadcast-fallback is set, but there is no IP address to unicast to for {}. Use --host, or add the IP address to the line in --file",
+            plural(without_ip, "target")
+        );
+        ExitCode::InvalidArguments.exit();
+    }
+    let mut broadcasts = HashMap::new();
+    let mut networks = if !needs_broadcast {
+        Vec::new()
+    } else if matches.contains_id("net") || matches.contains_id("net_file") {
+        let mut ip_nets = Vec::new();
+        for (net, location) in net_values(&matches) {
+            // auto adds the detected networks to the listed ones
+            if net == "auto" {
+                ip_nets.extend(detect_networks(&matches));
+                continue;
+            }
+            match parse_net(&net) {
+                Ok((ip_net, broadcast)) => {
+                    if let Some(broadcast) = broadcast {
+                        broadcasts.insert(ip_net, broadcast);
+                    }
+                    ip_nets.push(ip_net);
+                }
+                Err(err) => {
+                    match location {
+                        Some(location) => println!("Error: {}: {}", location, err),
+                        None => println!("Error: {}", err),
+                    }
+                    ExitCode::InvalidArguments.exit();
+                }
+            }
+        }
+        // a broadcast address listed twice only gets the packet once
+        let mut seen = HashSet::new();
+        ip_nets.retain(|ip_net| {
+            seen.insert(broadcasts.get(ip_net).copied().unwrap_or_else(|| ip_net.broadcast()))
+        });
+        ip_nets
+    } else if let Some(ip) = matches.get_one::<Ipv4Addr>("network_from_host") {
+        // the network enclosing the address, e.g. 192.168.50.0/24
+        let prefix_len = *matches.get_one::<u8>("prefix").unwrap();
+        vec![Ipv4Net::new(*ip, prefix_len).unwrap().trunc()]
+    } else {
+        detect_networks(&matches)
+    };
+    if needs_broadcast && networks.is_empty() {
+        println!("Error: no network to broadcast on was found, specify one with --net");
+        ExitCode::NoNetworks.exit();
+    }
+    if needs_broadcast && matches.get_flag("broadcast_only_reachable") {
+        networks.retain(|network| {
+            let broadcast = broadcasts.get(network).copied().unwrap_or_else(|| network.broadcast());
+            match ping::ping_broadcast(broadcast) {
+                ping::BroadcastPing::Answered => {
+                    if verbose_mode {
+                        println!("A host answered the ping to {}", broadcast);
+                    }
+                    true
+                }
+                ping::BroadcastPing::NoAnswer => {
+                    println!(
+                        "Warning: nothing answered a ping to {}, not broadcasting on {}",
+                        broadcast, network
+                    );
+                    false
+                }
+                ping::BroadcastPing::Unavailable(err) => {
+                    if verbose_mode {
+                        println!("Can't ping {}, broadcasting anyway: {}", broadcast, err);
+                    }
+                    true
+                }
+            }
+        });
+        if networks.is_empty() {
+            println!("Error: no network answered the broadcast ping, nothing was sent");
+            ExitCode::NoNetworks.exit();
+        }
+    }
+
+    // every target gets the packet on every listed port, typically 7 and 9
+    let mut ports: Vec<u16> = match matches.get_many::<u16>("port") {
+        Some(v) => v.copied().collect(),
+        None => vec![WOL_PORT],
+    };
+    ports.sort_unstable();
+    ports.dedup();
+    if verbose_mode {
+        let port_list: Vec<String> = ports.iter().map(|port| port.to_string()).collect();
+        let plural = if ports.len() > 1 { "s" } else { "" };
+        println!("Sending to UDP port{} {}", plural, port_list.join(", "));
+    }
+
+    let mut plan = WakePlan {
+        targets,
+        host_ip,
+        networks,
+        broadcasts,
+        ports,
+        password,
+        raw_payload,
+        payload_prefix,
+        packets,
+        truncated,
+        run_id: format!("{:08x}", Rng::from_time().next_u64() as u32),
+    };
+    if verbose_mode {
+        println!("Run ID {}", plan.run_id);
     }
-}
 
-fn send_magic_packet(
-    socket: &UdpSocket,
-    target_mac: &str,
-    ip_net: &Ipv4Net,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut mac_parts: Vec<&str> = target_mac.split(':').collect();
-    if mac_parts.len() != 6 {
-        mac_parts = target_mac.split('-').collect();
-        if mac_parts.len() != 6 {
-            return Err("Invalid MAC address format, should be 6 parts".into());
+    if matches.get_flag("arp_verify") {
+        arp_verify(&plan, mac_case);
+    }
+    if matches.get_flag("dump_config") {
+        println!("{}", dump_config(&matches, &plan));
+        ExitCode::Success.with_skipped(invalid_macs).exit();
+    }
+    if matches.get_flag("explain") {
+        println!("{}", explain(&matches, &plan));
+    }
+    if matches.get_flag("dry_run") {
+        for target in &plan.targets {
+            let mac_display = format_target_mac(target, mac_case);
+            for network in plan.destinations(target) {
+                for port in &plan.ports {
+                    let destination = if network.prefix_len() == 32 {
+                        format!("unicast to {}", network.addr())
+                    } else {
+                        format!("broadcast on {} ({})", network, plan.broadcast(&network))
+                    };
+                    println!(
+                        "Would send magic packet to {}, and {} port {}",
+                        mac_display, destination, port
+                    );
+                }
+            }
         }
+        ExitCode::Success.with_skipped(invalid_macs).exit();
     }
 
-    let mut mac_bytes = [0u8; 6];
-    for (i, part) in mac_parts.iter().enumerate() {
-        // parse the mac address
-        if part.len() != 2 {
-            return Err("Invalid MAC address format, should be 2 characters per part".into());
+    // broadcast is only enabled when something is broadcast, a sandbox may
+    // forbid it. When it does, the targets with an IP address are still woken
+    // by unicast
+    let bound = if no_broadcast || !needs_broadcast {
+        bind_unicast_socket()
+    } else {
+        match bind_socket() {
+            Err(WakeError::BroadcastNotPermitted(_))
+                if !server && plan.targets.iter().any(|target| target.ip.is_some()) =>
+            {
+                let without_ip = plan.targets.iter().filter(|target| target.ip.is_none()).count();
+                println!(
+                    "Warning: broadcast not permitted, unicasting to the targets with an IP address and skipping {} without one",
+                    plural(without_ip, "target")
+                );
+                plan.targets.retain(|target| target.ip.is_some());
+                plan.networks.clear();
+                keepalives.retain(|(target, _)| target.ip.is_some());
+                invalid_macs += without_ip;
+                bind_unicast_socket()
+            }
+            bound => bound,
         }
-        mac_bytes[i] = match u8::from_str_radix(part, 16) {
-            Ok(v) => v,
-            Err(_) => return Err("Invalid MAC address format, should be hex".into()),
+    };
+    let socket = match bound {
+        Ok(v) => v,
+        Err(WakeError::BroadcastNotPermitted(_)) => {
+            println!("Error: broadcast not permitted; try --host, or add the IP addresses to the lines in --file and use --no-broadcast-fallback, or run with the privileges needed to send broadcasts");
+            ExitCode::Io.exit();
         }
+        Err(err) => {
+            println!("Error: {}", err);
+            ExitCode::Io.exit();
+        }
+    };
+    let sockets = Sockets::bind(socket, &plan.networks, no_broadcast, verbose_mode);
+    let mut batcher = Batcher::new(&matches, &plan.networks);
+    // a --retry-on-down host that doesn't resolve is reported before the
+    // server or the watch start, not at their first wake
+    if let Err(err) = read_probe(&matches) {
+        println!("Error: {}", err);
+        ExitCode::InvalidArguments.exit();
     }
 
-    let mut magic_packet = [0u8; 102];
+    // --max-runtime bounds the long running modes
+    let run_start = Instant::now();
+    let deadline = matches
+        .get_one::<Duration>("max_runtime")
+        .map(|max_runtime| run_start + *max_runtime);
 
-    magic_packet[..6].copy_from_slice(&MAGIC_HEADER);
+    if server {
+        let socket_path = server_socket(&matches);
+        println!("Listening on {:?}, press Ctrl-C to stop", socket_path);
+        let mut wakes = 0;
+        let served = server::serve(&socket_path, deadline, |line| {
+            let (targets, line_texts): (Vec<Target>, MacTexts) = match parse_target_line(line) {
+                Ok(Some(v)) => v
+                    .targets
+                    .into_iter()
+                    .map(|(target, text)| {
+                        let mac = target.mac;
+                        (target, (mac, text))
+                    })
+                    .unzip(),
+                Ok(None) => return None,
+                Err(err) => return Some(format!("error {}: {}", line, err)),
+            };
+            wakes += 1;
+            println!(
+                "{} waking {} for a client",
+                Local::now().format("%Y-%m-%d %H:%M:%S"),
+                line
+            );
+            let line_plan = WakePlan {
+                targets,
+                ..plan.clone()
+            };
+            let line_case = mac_case.with_texts(&line_texts);
+            let exit_code =
+                wake(&matches, &sockets, &mut batcher, &line_plan, Instant::now(), line_case);
+            Some(match exit_code {
+                Ok(ExitCode::Success) => format!("ok {}", line),
+                Ok(exit_code) => format!("failed {}: exit code {}", line, exit_code as i32),
+                Err(err) => {
+                    println!("Error: {}", err);
+                    format!("failed {}: {}", line, err)
+                }
+            })
+        });
+        if let Err(err) = served {
+            println!("Error: failed to listen on {:?}: {}", socket_path, err);
+            ExitCode::Io.exit();
+        }
+        println!(
+            "Stopped listening after {:.0?}, {} requested",
+            run_start.elapsed(),
+            plural(wakes, "wake")
+        );
+        // --max-runtime is a clean stop, Ctrl-C is not
+        if signal::interrupted() {
+            ExitCode::Interrupted.exit();
+        }
+        ExitCode::Success.exit();
+    }
 
-    for i in 0..16 {
-        magic_packet[6 + i * 6..6 + (i + 1) * 6].copy_from_slice(&mac_bytes);
+    if let Some(watch_path) = matches.get_one::<PathBuf>("watch") {
+        let debounce = *matches.get_one::<Duration>("watch_debounce").unwrap();
+        println!("Watching {:?}, press Ctrl-C to stop", watch_path);
+        let mut wakes = 0;
+        let watched = watch::watch(watch_path, debounce, deadline, || {
+            wakes += 1;
+            println!(
+                "{} {:?} changed, waking {}",
+                Local::now().format("%Y-%m-%d %H:%M:%S"),
+                watch_path,
+                plural(plan.targets.len(), "device")
+            );
+            let woken = wake(&matches, &sockets, &mut batcher, &plan, Instant::now(), mac_case);
+            if let Err(err) = woken {
+                println!("Error: {}", err);
+            }
+        });
+        if let Err(err) = watched {
+            println!("Error: failed to watch {:?}: {}", watch_path, err);
+            ExitCode::Io.exit();
+        }
+        println!(
+            "Stopped watching after {:.0?}, {} triggered",
+            run_start.elapsed(),
+            plural(wakes, "wake")
+        );
+        // --max-runtime is a clean stop, Ctrl-C is not
+        if signal::interrupted() {
+            ExitCode::Interrupted.exit();
+        }
+        ExitCode::Success.exit();
     }
 
-    let broadcast_address = ip_net.broadcast();
+    let mut exit_code = match wake(&matches, &sockets, &mut batcher, &plan, start_time, mac_case) {
+        Ok(v) => v.with_skipped(invalid_macs),
+        Err(err) => {
+            println!("Error: {}", err);
+            ExitCode::InvalidArguments.exit();
+        }
+    };
 
-    let dest = SocketAddr::new(broadcast_address.into(), WOL_PORT);
+    if let Some(destination) = matches.get_one::<String>("wake_and_ssh") {
+        if exit_code == ExitCode::TotalFailure {
+            exit_code.exit();
+        }
+        wake_and_ssh(&matches, destination, &plan.run_id);
+    }
 
-    socket.set_broadcast(true)?;
-    socket.send_to(&magic_packet, dest)?;
+    if !keepalives.is_empty() {
+        keep_awake(&sockets, &mut batcher, &keepalives, &plan, deadline, mac_case, verbose_mode);
+        if signal::interrupted() {
+            exit_code = ExitCode::Interrupted;
+        }
+    }
 
-    Ok(())
+    // some USB network adapters drop packets whose socket closes right away
+    if let Some(hold_open) = matches.get_one::<Duration>("hold_open") {
+        if verbose_mode {
+            println!("Keeping the socket open for {:?}", hold_open);
+        }
+        signal::sleep(*hold_open);
+    }
+
+    exit_code.exit();
+}
+
+// the targets and where their packets go, resolved from the arguments
+#[derive(Clone)]
+struct WakePlan {
+    targets: Vec<Target>,
+    host_ip: Option<Ipv4Addr>,
+    networks: Vec<Ipv4Net>,
+    // broadcast addresses given with --net CIDR@BROADCAST
+    broadcasts: HashMap<Ipv4Net, Ipv4Addr>,
+    ports: Vec<u16>,
+    // SecureOn password appended to every packet
+    password: Option<Vec<u8>>,
+    // --raw-payload, sent instead of the magic packet
+    raw_payload: Option<Vec<u8>>,
+    // --payload-prefix, sent before the magic packet
+    payload_prefix: Option<Vec<u8>>,
+    // the --file-format hex-lines packets, sent as they are to the MAC
+    // address they wake instead of any of the above
+    packets: HashMap<MacAddr, Vec<u8>>,
+    // how many targets --count left out
+    truncated: usize,
+    // random, to tell the output of concurrent runs apart
+    run_id: String,
 }
 
-fn get_local_ip_nets() -> Vec<Ipv4Net> {
-    let if_addrs = get_if_addrs().unwrap();
-    let mut ip_nets = Vec::new();
+// send to every target of `plan` once, including repeats and retries, and
+// report the results. Fails before sending anything when --retry-on-down
+// doesn't resolve
+fn wake(
+    matches: &clap::ArgMatches,
+    sockets: &Sockets,
+    batcher: &mut Batcher,
+    plan: &WakePlan,
+    start_time: Instant,
+    mac_case: MacCase,
+) -> Result<ExitCode, String> {
+    let verbose_mode = matches.get_flag("verbose");
+    let compact = matches.get_flag("compact");
+    let timing = matches.get_flag("timing");
+    let template = matches.get_one::<Template>("template");
+    let retry_networks = *matches.get_one::<u32>("retry_networks").unwrap();
+    let host_ip = plan.host_ip;
+    let networks = &plan.networks;
+    let ports = &plan.ports;
 
-    for if_addr in if_addrs {
-        if let if_addrs::IfAddr::V4(if_v4_addr) = if_addr.addr {
-            let ip = if_v4_addr.ip;
-            let netmask = if_v4_addr.netmask;
-            let prefix_len = netmask
-                .octets()
+    // with --retry-on-down, resending stops once the host answers
+    let probe = read_probe(matches)?;
+    let mut host_up = false;
+    let mut probe_up = |what: &str| {
+        let addrs = match &probe {
+            Some(v) => v,
+            None => return false,
+        };
+        host_up = host_up || host_is_up(addrs);
+        if host_up {
+            println!("{} is up, skipping the remaining {}", format_probe(addrs), what);
+        } else if verbose_mode {
+            println!("{} is still down", format_probe(addrs));
+        }
+        host_up
+    };
+
+    // with --min-interval, skip the targets that were woken too recently
+    let now = SystemTime::now();
+    let min_interval = matches.get_one::<Duration>("min_interval").copied();
+    let mut history = min_interval.map(|_| {
+        let path = matches
+            .get_one::<PathBuf>("state_file")
+            .cloned()
+            .unwrap_or_else(history::default_path);
+        history::WakeHistory::load(&path).unwrap_or_else(|err| {
+            println!("Warning: failed to read {:?}, starting a new one: {}", path, err);
+            history::WakeHistory::new(&path)
+        })
+    });
+    let targets = not_recently_woken(
+        &plan.targets,
+        history.as_ref().zip(min_interval),
+        now,
+        compact,
+        mac_case,
+    );
+    let rate_limited = plan.targets.len() - targets.len();
+    // the outcomes below follow this list, not plan.targets
+    let targets = &targets;
+
+    // every wake pauses after its own --batch-size packets
+    batcher.sent = 0;
+
+    let mut static_arp = arp::StaticEntries::new();
+    add_static_arp_entries(&mut static_arp, targets, host_ip, verbose_mode);
+    // Ctrl-C would leave the added entries behind, so catch it and stop
+    // sending early instead
+    if !static_arp.is_empty() {
+        signal::install();
+    }
+
+    let mut send_times: Vec<Duration> = Vec::new();
+    let mut send = |step: &SendStep, results: &mut Vec<NetworkResult>| {
+        let socket = sockets.get(step.network);
+        let broadcast = plan.broadcast(step.network);
+        let result = batcher.send(socket, step.packet, step.network, broadcast, step.port);
+        record_send(results, step, result, &mut |event| {
+            if let WakeEvent::Sent { result, .. } = event {
+                send_times.push(result.elapsed);
+            }
+        });
+    };
+
+    // send to every target in the order the library sends in. With
+    // --confirm-each the question comes right before the target's sends, and
+    // a target that isn't confirmed gets none
+    let confirm_each = matches.get_flag("confirm_each") && !matches.get_flag("yes");
+    let mut aborted = false;
+    let mut sends: Vec<(Vec<Ipv4Net>, Vec<u8>)> = Vec::new();
+    let mut results = Vec::new();
+    for (index, target) in targets.iter().enumerate() {
+        if confirm_each && !aborted {
+            match ask_to_wake(plan, target, mac_case) {
+                Answer::Yes => {}
+                Answer::No => {
+                    sends.push((Vec::new(), Vec::new()));
+                    continue;
+                }
+                Answer::Quit => {
+                    println!("Skipping the remaining targets");
+                    aborted = true;
+                }
+            }
+        }
+        if aborted {
+            sends.push((Vec::new(), Vec::new()));
+            continue;
+        }
+        sends.push((plan.destinations(target), plan.packet(&target.mac)));
+        for step in send_steps(&targets[index..=index], &sends[index..], ports, 1) {
+            send(&step, &mut results);
+        }
+    }
+
+    // then everything again --repeat times, a pair counts as sent when any of
+    // its repeats succeeded
+    let repeat_count = *matches.get_one::<u32>("repeat").unwrap();
+    let delays = repeat::repeat_delays(
+        repeat_count,
+        *matches.get_one::<Duration>("delay").unwrap(),
+        matches.get_flag("backoff"),
+        matches.get_one::<Duration>("max_delay").copied(),
+    );
+    let repeats = send_steps(targets, &sends, ports, repeat_count).filter(|step| step.attempt > 1);
+    for step in repeats {
+        if step.pauses_before() {
+            let delay = delays[step.attempt as usize - 2];
+            if verbose_mode {
+                println!("Repeat {} of {} in {:?}", step.attempt, repeat_count, delay);
+            }
+            if !signal::sleep(delay) || probe_up("repeats") {
+                break;
+            }
+        }
+        send(&step, &mut results);
+    }
+    // the results come in target order, one per network and port
+    let mut results = results.into_iter();
+    let mut outcomes: Vec<(MacAddr, Vec<NetworkResult>)> = targets
+        .iter()
+        .zip(&sends)
+        .map(|(target, (networks, _))| {
+            let sent = results.by_ref().take(networks.len() * ports.len()).collect();
+            (target.mac, sent)
+        })
+        .collect();
+
+    // retry only the (MAC, network) pairs that failed, so networks that
+    // already got the packet don't receive it again
+    let mut retries = HashMap::new();
+    if retry_networks > 0 {
+        for attempt in 1..=retry_networks {
+            let any_failed = outcomes
                 .iter()
-                .fold(0, |acc, &octet| acc + octet.count_ones() as u8);
-            let ip_net = Ipv4Net::new(ip, prefix_len).unwrap();
-            ip_nets.push(ip_net);
+                .any(|(_, results)| results.iter().any(|net_result| net_result.result.is_err()));
+            if signal::interrupted() || (any_failed && probe_up("retries")) {
+                break;
+            }
+            let mut any_failed = false;
+            for (mac_index, (mac_address, results)) in outcomes.iter_mut().enumerate() {
+                for (net_index, net_result) in results.iter_mut().enumerate() {
+                    if net_result.result.is_err() {
+                        *net_result = batcher.send(
+                            sockets.get(&net_result.network),
+                            &plan.packet(mac_address),
+                            &net_result.network,
+                            plan.broadcast(&net_result.network),
+                            net_result.port,
+                        );
+                        send_times.push(net_result.elapsed);
+                        retries.insert((mac_index, net_index), attempt);
+                        any_failed |= net_result.result.is_err();
+                    }
+                }
+            }
+            if !any_failed {
+                break;
+            }
         }
     }
 
-    ip_nets
-}
+    if matches.get_flag("keep_arp") {
+        for ip in static_arp.keep() {
+            if verbose_mode {
+                println!("Keeping the ARP entry for {}", ip);
+            }
+        }
+    }
+    for (ip, err) in static_arp.cleanup() {
+        println!("Warning: failed to remove the ARP entry for {}: {}", ip, err);
+    }
+
+    if let Some(history) = history.as_mut() {
+        for (mac_address, results) in &outcomes {
+            if results.iter().any(|net_result| net_result.result.is_ok()) {
+                history.record(*mac_address, now);
+            }
+        }
+        if let Err(err) = history.save() {
+            println!("Warning: failed to save the wake history: {}", err);
+        }
+    }
 
-fn read_mac_addresses_from_file(file_path: &PathBuf) -> Vec<String> {
-    let file = File::open(file_path).unwrap();
-    let reader = BufReader::new(file);
-    let mut mac_addresses = Vec::new();
+    // a host that answered the probe is awake, whatever happened to the sends
+    let results = outcomes.iter().flat_map(|(_, results)| results);
+    let exit_code = if host_up {
+        ExitCode::Success
+    } else {
+        ExitCode::from_sends(
+            results.clone().count(),
+            results.filter(|net_result| net_result.result.is_err()).count(),
+        )
+    };
 
-    for line in reader.lines() {
-        let line = match line {
-            Ok(v) => v.trim().to_string(),
-            Err(_) => break, // if read line error, break the loop
+    if compact {
+        // a target counts as sent when at least one network got its packet
+        let sent = outcomes
+            .iter()
+            .filter(|(_, results)| results.iter().any(|net_result| net_result.result.is_ok()))
+            .count();
+        let timing_stats = if timing {
+            format!(" ({})", format_timing_stats(&send_times))
+        } else {
+            String::new()
         };
-        // skip empty lines
-        if line.is_empty() {
-            continue;
+        let skipped = if rate_limited > 0 {
+            format!(", {} skipped by --min-interval", rate_limited)
+        } else {
+            String::new()
+        };
+        let truncated = if plan.truncated > 0 {
+            format!(", {} left out by --count", plan.truncated)
+        } else {
+            String::new()
+        };
+        println!(
+            "wol: {}/{} sent across {} networks in {}ms{}{}{}",
+            sent,
+            outcomes.len(),
+            networks.len(),
+            start_time.elapsed().as_millis(),
+            skipped,
+            truncated,
+            timing_stats
+        );
+    } else {
+        // one line per (target, network, port), by target unless
+        // --group-by-network
+        let mut order: Vec<(usize, usize)> = outcomes
+            .iter()
+            .enumerate()
+            .flat_map(|(mac_index, (_, results))| {
+                (0..results.len()).map(move |net_index| (mac_index, net_index))
+            })
+            .collect();
+        if matches.get_flag("group_by_network") {
+            // networks come in the order they were first sent to, the sort is
+            // stable so targets keep their order within a network
+            let mut network_ranks: HashMap<Ipv4Net, usize> = HashMap::new();
+            for &(mac_index, net_index) in &order {
+                let rank = network_ranks.len();
+                network_ranks
+                    .entry(outcomes[mac_index].1[net_index].network)
+                    .or_insert(rank);
+            }
+            order.sort_by_key(|&(mac_index, net_index)| {
+                network_ranks[&outcomes[mac_index].1[net_index].network]
+            });
         }
-        // skip line starts with #
-        if line.starts_with('#') {
-            continue;
+        for (mac_index, net_index) in order {
+            let results = &outcomes[mac_index].1;
+            let mac_display = format_target_mac(&targets[mac_index], mac_case);
+            let net_result = &results[net_index];
+            let retried = match retries.get(&(mac_index, net_index)) {
+                Some(1) => " (after 1 retry)".to_string(),
+                Some(attempts) => format!(" (after {} retries)", attempts),
+                None => String::new(),
+            };
+            let elapsed = if timing {
+                format!(" in {:.3?}", net_result.elapsed)
+            } else {
+                String::new()
+            };
+            let port = if ports.len() > 1 {
+                format!(" port {}", net_result.port)
+            } else {
+                String::new()
+            };
+            let destination = if net_result.network.prefix_len() == 32 {
+                format!("unicast to {}", net_result.network.addr())
+            } else {
+                format!("broadcasted on {}", net_result.network)
+            };
+            if let Some(template) = template {
+                let result = match &net_result.result {
+                    Ok(_) => "ok".to_string(),
+                    Err(err) => err.to_string(),
+                };
+                println!(
+                    "{}",
+                    template.render(&TemplateValues {
+                        mac: &mac_display,
+                        name: targets[mac_index].name.as_deref().unwrap_or(""),
+                        network: &net_result.network.to_string(),
+                        broadcast: &plan.broadcast(&net_result.network).to_string(),
+                        port: net_result.port,
+                        result: &result,
+                        time: &format!("{:.3?}", net_result.elapsed),
+                        run_id: &plan.run_id,
+                    })
+                );
+                continue;
+            }
+            match &net_result.result {
+                Ok(_) => {
+                    if verbose_mode || timing {
+                        println!(
+                            "Sent magic packet to {}, and {}{}{}{}",
+                            mac_display, destination, port, retried, elapsed
+                        );
+                    }
+                }
+                Err(err) => {
+                    println!(
+                        "Error: {}, original MAC address: {}, network: {}{}{}{}",
+                        err, mac_display, net_result.network, port, retried, elapsed
+                    );
+                }
+            }
         }
-        // skip line starts with //
-        if line.starts_with("//") {
-            continue;
+
+        if timing {
+            println!(
+                "Timing: {}, total {:.3?}",
+                format_timing_stats(&send_times),
+                start_time.elapsed()
+            );
         }
-        // check the mac address length (should be 17)
-        if line.len() != 17 {
+    }
+
+    run_result_hooks(matches, targets, &plan.run_id, &outcomes, mac_case);
+
+    if matches.get_flag("stats_json") {
+        println!(
+            "{}",
+            stats_json(targets, &plan.run_id, &outcomes, networks.len(), start_time)
+        );
+    }
+
+    if signal::interrupted() {
+        println!("Interrupted, the remaining repeats and retries were not sent");
+        return Ok(ExitCode::Interrupted);
+    }
+    Ok(exit_code)
+}
+
+// the addresses --retry-on-down probes, resolved for every wake since a
+// long running server can outlive the host's address
+fn read_probe(matches: &clap::ArgMatches) -> Result<Option<Vec<SocketAddr>>, String> {
+    let host = match matches.get_one::<String>("retry_on_down") {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+    let probe_ports: Vec<u16> = match matches.get_many::<u16>("probe_port") {
+        Some(v) => v.copied().collect(),
+        None => vec![22],
+    };
+    match resolve_probe(host, &probe_ports) {
+        Ok(v) => Ok(Some(v)),
+        Err(err) => Err(format!("failed to resolve --retry-on-down {}: {}", host, err)),
+    }
+}
+
+// the targets --min-interval doesn't skip, `history` holds when they were
+// last woken and the window
+fn not_recently_woken(
+    targets: &[Target],
+    history: Option<(&history::WakeHistory, Duration)>,
+    now: SystemTime,
+    compact: bool,
+    mac_case: MacCase,
+) -> Vec<Target> {
+    targets
+        .iter()
+        .filter(|target| {
+            let woken_ago = history
+                .and_then(|(history, window)| history.woken_within(&target.mac, window, now));
+            let Some(ago) = woken_ago else {
+                return true;
+            };
+            if !compact {
+                println!(
+                    "Skipped {}, it was woken {}s ago, within --min-interval",
+                    format_target_mac(target, mac_case),
+                    ago.as_secs()
+                );
+            }
+            false
+        })
+        .cloned()
+        .collect()
+}
+
+// run --on-success for every target that got at least one packet and
+// --on-failure for the others, with the result in the environment.
+// `outcomes` follows `targets`
+fn run_result_hooks(
+    matches: &clap::ArgMatches,
+    targets: &[Target],
+    run_id: &str,
+    outcomes: &[(MacAddr, Vec<NetworkResult>)],
+    mac_case: MacCase,
+) {
+    let on_success = matches.get_one::<String>("on_success");
+    let on_failure = matches.get_one::<String>("on_failure");
+    if on_success.is_none() && on_failure.is_none() {
+        return;
+    }
+
+    let mut hooks = Vec::new();
+    for (target, (_, results)) in targets.iter().zip(outcomes) {
+        // skipped by --confirm-each
+        if results.is_empty() {
             continue;
         }
-        // invalid MAC address format (should be separated by :)
-        if !line.contains(':') && !line.contains('-') {
+        let failed = results
+            .iter()
+            .filter(|net_result| net_result.result.is_err())
+            .count();
+        let succeeded = failed < results.len();
+        let (command, flag) = if succeeded {
+            (on_success, "--on-success")
+        } else {
+            (on_failure, "--on-failure")
+        };
+        let Some(command) = command else {
             continue;
-        }
-        mac_addresses.push(line);
+        };
+        let result = match results.iter().find_map(|net_result| net_result.result.as_ref().err()) {
+            Some(err) if !succeeded => err.to_string(),
+            _ => "ok".to_string(),
+        };
+        let mac_display = format_target_mac(target, mac_case);
+        hooks.push(hooks::Hook {
+            command: command.clone(),
+            env: vec![
+                ("WOL_MAC", mac_display.clone()),
+                ("WOL_NAME", target.name.clone().unwrap_or_default()),
+                ("WOL_RESULT", result),
+                ("WOL_SENT", (results.len() - failed).to_string()),
+                ("WOL_FAILED", failed.to_string()),
+                ("WOL_RUN_ID", run_id.to_string()),
+            ],
+            label: format!("{} hook for {}", flag, mac_display),
+        });
     }
+    for (label, err) in hooks::run_all(hooks) {
+        println!("Warning: {} failed: {}", label, err);
+    }
+}
+
+// the --stats-json roll-up, the keys are documented in the README and must
+// stay stable. `outcomes` follows `targets`
+fn stats_json(
+    targets: &[Target],
+    run_id: &str,
+    outcomes: &[(MacAddr, Vec<NetworkResult>)],
+    networks: usize,
+    start_time: Instant,
+) -> String {
+    let mut sent_total = 0;
+    let mut failed_total = 0;
+    let mut per_target = Vec::new();
+    for (target, (mac_address, results)) in targets.iter().zip(outcomes) {
+        let failed = results
+            .iter()
+            .filter(|net_result| net_result.result.is_err())
+            .count();
+        let sent = results.len() - failed;
+        sent_total += sent;
+        failed_total += failed;
+        let name = match &target.name {
+            Some(name) => json_string(name),
+            None => "null".to_string(),
+        };
+        per_target.push(format!(
+            "{{\"mac\":\"{}\",\"name\":{},\"sent\":{},\"failed\":{}}}",
+            mac_address, name, sent, failed
+        ));
+    }
+    format!(
+        "{{\"run_id\":\"{}\",\"sent_total\":{},\"failed_total\":{},\"networks\":{},\"duration_ms\":{},\"targets\":[{}]}}",
+        run_id,
+        sent_total,
+        failed_total,
+        networks,
+        start_time.elapsed().as_millis(),
+        per_target.join(",")
+    )
+}
+
+// the --dump-config JSON, the settings the run would use once every flag,
+// file and environment variable is resolved. The password itself is left out
+fn dump_config(matches: &clap::ArgMatches, plan: &WakePlan) -> String {
+    let json_or_null = |value: Option<String>| value.unwrap_or_else(|| "null".to_string());
+    let targets: Vec<String> = plan
+        .targets
+        .iter()
+        .map(|target| {
+            format!(
+                "{{\"mac\":\"{}\",\"ip\":{},\"name\":{}}}",
+                target.mac,
+                json_or_null(target.ip.map(|ip| json_string(&ip.to_string()))),
+                json_or_null(target.name.as_deref().map(json_string))
+            )
+        })
+        .collect();
+    let networks: Vec<String> = plan
+        .networks
+        .iter()
+        .map(|network| {
+            format!(
+                "{{\"network\":\"{}\",\"broadcast\":\"{}\"}}",
+                network,
+                plan.broadcast(network)
+            )
+        })
+        .collect();
+    let ports: Vec<String> = plan.ports.iter().map(u16::to_string).collect();
+    let millis = |id: &str| {
+        json_or_null(
+            matches
+                .get_one::<Duration>(id)
+                .map(|duration| duration.as_millis().to_string()),
+        )
+    };
+
+    let fields = [
+        ("targets", format!("[{}]", targets.join(","))),
+        ("host", json_or_null(plan.host_ip.map(|ip| json_string(&ip.to_string())))),
+        ("networks", format!("[{}]", networks.join(","))),
+        ("ports", format!("[{}]", ports.join(","))),
+        ("password", plan.password.is_some().to_string()),
+        ("raw_payload", plan.raw_payload.is_some().to_string()),
+        (
+            "payload_prefix",
+            json_or_null(plan.payload_prefix.as_ref().map(|prefix| json_string(&hex(prefix)))),
+        ),
+        ("repeat", matches.get_one::<u32>("repeat").unwrap().to_string()),
+        ("delay_ms", millis("delay")),
+        ("backoff", matches.get_flag("backoff").to_string()),
+        ("max_delay_ms", millis("max_delay")),
+        ("burst", matches.get_one::<u32>("burst").unwrap().to_string()),
+        (
+            "batch_size",
+            json_or_null(matches.get_one::<u64>("batch_size").map(u64::to_string)),
+        ),
+        ("batch_pause_ms", millis("batch_pause")),
+        ("retry_networks", matches.get_one::<u32>("retry_networks").unwrap().to_string()),
+    ];
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(key, value)| format!("  \"{}\": {}", key, value))
+        .collect();
+    format!("{{\n{}\n}}", fields.join(",\n"))
+}
+
+// a JSON string literal
+fn json_string(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+impl WakePlan {
+    fn packet(&self, mac_address: &MacAddr) -> Vec<u8> {
+        if let Some(packet) = self.packets.get(mac_address) {
+            return packet.clone();
+        }
+        build_packet(
+            mac_address,
+            self.password.as_deref(),
+            self.raw_payload.as_deref(),
+            self.payload_prefix.as_deref(),
+        )
+    }
+
+    // a unicast target is sent to its own address as a /32 network, whose
+    // broadcast address is the host itself
+    fn destinations(&self, target: &Target) -> Vec<Ipv4Net> {
+        match target.ip.or(self.host_ip) {
+            Some(ip) => vec![Ipv4Net::from(ip)],
+            None => self.networks.clone(),
+        }
+    }
+
+    // where packets for `network` go, its broadcast address unless --net
+    // overrides it
+    fn broadcast(&self, network: &Ipv4Net) -> Ipv4Addr {
+        self.broadcasts
+            .get(network)
+            .copied()
+            .unwrap_or_else(|| network.broadcast())
+    }
+}
+
+// say where the ARP cache last saw every target, and warn when that is on
+// none of the networks its packet is sent to
+fn arp_verify(plan: &WakePlan, mac_case: MacCase) {
+    let table = match arp::read_arp_table() {
+        Ok(v) => v,
+        Err(err) => {
+            println!("Warning: --arp-verify can't read the ARP cache: {}", err);
+            return;
+        }
+    };
+    for target in &plan.targets {
+        let mac_display = format_target_mac(target, mac_case);
+        let destinations = plan.destinations(target);
+        let seen_at: Vec<Ipv4Addr> = table
+            .iter()
+            .filter(|entry| entry.mac == target.mac)
+            .map(|entry| entry.ip)
+            .collect();
+        if seen_at.is_empty() {
+            println!("ARP: {} is not in the ARP cache", mac_display);
+            continue;
+        }
+        for ip in seen_at {
+            match destinations.iter().find(|network| network.contains(&ip)) {
+                Some(network) => println!("ARP: {} is at {}, on {}", mac_display, ip, network),
+                None => println!(
+                    "Warning: ARP: {} is at {}, which is on none of the networks it is sent to",
+                    mac_display, ip
+                ),
+            }
+        }
+    }
+}
+
+// the bytes of a --raw-payload file, which must not be empty
+fn read_raw_payload(path: &Path) -> Vec<u8> {
+    let payload = match std::fs::read(path) {
+        Ok(v) => v,
+        Err(err) => {
+            println!("Error: failed to read raw payload {:?}: {}", path, err);
+            ExitCode::Io.exit();
+        }
+    };
+    if payload.is_empty() {
+        println!("Error: raw payload {:?} is empty", path);
+        ExitCode::InvalidArguments.exit();
+    }
+    if !payload.starts_with(&MAGIC_HEADER) {
+        println!(
+            "Warning: raw payload {:?} doesn't start with the magic packet header",
+            path
+        );
+    }
+    payload
+}
+
+// the bytes sent to `mac_address`, --raw-payload or the magic packet with
+// the password and --payload-prefix
+fn build_packet(
+    mac_address: &MacAddr,
+    password: Option<&[u8]>,
+    raw_payload: Option<&[u8]>,
+    payload_prefix: Option<&[u8]>,
+) -> Vec<u8> {
+    if let Some(payload) = raw_payload {
+        return payload.to_vec();
+    }
+    let magic_packet = match password {
+        Some(password) => build_magic_packet_with_password(mac_address, password),
+        None => build_magic_packet(mac_address),
+    };
+    match payload_prefix {
+        Some(prefix) => [prefix, &magic_packet].concat(),
+        None => magic_packet,
+    }
+}
+
+// the SecureOn password from --password, --password-file or $WOL_PASSWORD,
+// in that order
+fn read_password(matches: &clap::ArgMatches) -> Option<Vec<u8>> {
+    let (password, source) = if let Some(password) = matches.get_one::<String>("password") {
+        (password.clone(), "--password".to_string())
+    } else if let Some(path) = matches.get_one::<PathBuf>("password_file") {
+        match std::fs::read_to_string(path) {
+            Ok(v) => (v.trim().to_string(), format!("{:?}", path)),
+            Err(err) => {
+                println!("Error: failed to read password file {:?}: {}", path, err);
+                ExitCode::Io.exit();
+            }
+        }
+    } else if let Some(password) = std::env::var_os("WOL_PASSWORD") {
+        (password.to_string_lossy().trim().to_string(), "WOL_PASSWORD".to_string())
+    } else {
+        return None;
+    };
+    match parse_password(&password) {
+        Ok(v) => Some(v),
+        Err(err) => {
+            println!("Error: {} in {}", err, source);
+            ExitCode::InvalidArguments.exit();
+        }
+    }
+}
+
+// 6 hex bytes written like a MAC address, or 4 bytes written like an IPv4
+// address
+fn parse_password(password: &str) -> Result<Vec<u8>, String> {
+    if let Ok(mac) = password.parse::<MacAddr>() {
+        return Ok(mac.octets().to_vec());
+    }
+    if let Ok(ip) = password.parse::<Ipv4Addr>() {
+        return Ok(ip.octets().to_vec());
+    }
+    Err("invalid SecureOn password, should be 6 hex bytes like DE:AD:BE:EF:00:01 \
+         or 4 bytes like 192.168.1.1"
+        .to_string())
+}
+
+// bytes written as pairs of hex digits, optionally separated by colons or
+// spaces, e.g. 574f4c or 57:4F:4C
+fn parse_hex(value: &str) -> Result<Vec<u8>, String> {
+    let digits: String = value
+        .chars()
+        .filter(|c| *c != ':' && !c.is_whitespace())
+        .collect();
+    if digits.is_empty() {
+        return Err("no bytes given".to_string());
+    }
+    if digits.len() % 2 != 0 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!(
+            "invalid hex bytes {:?}, should be pairs of hex digits like 574f4c",
+            value
+        ));
+    }
+    Ok((0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap())
+        .collect())
+}
+
+// a --file-format hex-lines line, a whole packet in hex and the MAC address
+// it wakes. Blank lines and comments are skipped like in a targets file
+fn parse_packet_line(line: &str) -> Result<Option<(MacAddr, Vec<u8>)>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with(['#', ';']) || line.starts_with("//") {
+        return Ok(None);
+    }
+    let packet = parse_hex(line)?;
+    match packet_mac(&packet) {
+        Some(mac) => Ok(Some((mac, packet))),
+        None => Err(format!(
+            "{} bytes that aren't a magic packet, there is no header followed by 16 copies \
+             of a MAC address",
+            packet.len()
+        )),
+    }
+}
+
+// the MAC address a magic packet wakes, the header can come after a prefix
+// and the copies can be followed by a password
+fn packet_mac(packet: &[u8]) -> Option<MacAddr> {
+    (0..packet.len()).find_map(|start| {
+        let copies = packet[start..].strip_prefix(&MAGIC_HEADER)?.get(..96)?;
+        let mac = &copies[..6];
+        if !copies.chunks(6).all(|copy| copy == mac) {
+            return None;
+        }
+        Some(MacAddr::new(mac.try_into().unwrap()))
+    })
+}
+
+// a --oui-prefix value, 3 hex bytes like 00:1A:2B, 00-1a-2b or 001a2b
+fn parse_oui(prefix: &str) -> Result<[u8; 3], String> {
+    let digits: String = prefix.chars().filter(|c| !matches!(c, ':' | '-' | '.')).collect();
+    if digits.len() != 6 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!(
+            "invalid --oui-prefix {}, should be 3 hex bytes like 00:1A:2B",
+            prefix
+        ));
+    }
+    let byte = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).unwrap();
+    Ok([byte(0), byte(2), byte(4)])
+}
+
+// --list-vendors, how many targets have every vendor prefix, the most common
+// first. There is no vendor database, so a vendor is its OUI
+fn list_vendors(targets: &[Target], mac_case: MacCase) {
+    let mut counts: HashMap<[u8; 3], usize> = HashMap::new();
+    for target in targets {
+        *counts.entry(target.mac.oui()).or_insert(0) += 1;
+    }
+    let mut counts: Vec<([u8; 3], usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    for (oui, count) in counts {
+        let oui = format!("{:02X}:{:02X}:{:02X}", oui[0], oui[1], oui[2]);
+        let oui = if mac_case == MacCase::Lower { oui.to_lowercase() } else { oui };
+        println!("{}: {}", oui, count);
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// the --net values followed by the networks of --net-file, each with the
+// file and line it comes from. Blank lines and comments are skipped like in
+// a targets file
+fn net_values(matches: &clap::ArgMatches) -> Vec<(String, Option<String>)> {
+    let mut values: Vec<(String, Option<String>)> = matches
+        .get_many::<String>("net")
+        .unwrap_or_default()
+        .map(|net| (net.clone(), None))
+        .collect();
+    if let Some(path) = matches.get_one::<PathBuf>("net_file") {
+        let content = match std::fs::read_to_string(path) {
+            Ok(v) => v,
+            Err(err) => {
+                println!("Error: failed to read {:?}: {}", path, err);
+                ExitCode::Io.exit();
+            }
+        };
+        for (line_index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(['#', ';']) || line.starts_with("//") {
+                continue;
+            }
+            let location = format!("{:?} line {}", path, line_index + 1);
+            values.push((line.to_string(), Some(location)));
+        }
+    }
+    values
+}
+
+// a --net value, CIDR@BROADCAST gives an explicit broadcast address
+fn parse_net(net: &str) -> Result<(Ipv4Net, Option<Ipv4Addr>), String> {
+    let (cidr, broadcast) = match net.split_once('@') {
+        Some((cidr, broadcast)) => (cidr, Some(broadcast)),
+        None => (net, None),
+    };
+    let ip_net = cidr.parse::<Ipv4Net>().map_err(|err| {
+        format!(
+            "{}. Correct address in CIDR notation, e.g. 192.168.1.0/24",
+            WakeError::from(err)
+        )
+    })?;
+    let broadcast = match broadcast {
+        Some(broadcast) => Some(broadcast.parse::<Ipv4Addr>().map_err(|_| {
+            format!(
+                "invalid broadcast address {} in --net {}, e.g. 192.168.1.0/24@192.168.1.255",
+                broadcast, net
+            )
+        })?),
+        None => None,
+    };
+    Ok((ip_net, broadcast))
+}
+
+// re-send to the targets with a keepalive interval until Ctrl-C, with the
+// same --burst, --simulate-loss, --write-pcap and carrier check as the wake
+fn keep_awake(
+    sockets: &Sockets,
+    batcher: &mut Batcher,
+    keepalives: &[(Target, Duration)],
+    plan: &WakePlan,
+    deadline: Option<Instant>,
+    mac_case: MacCase,
+    verbose_mode: bool,
+) {
+    println!(
+        "Keeping {} device(s) awake, press Ctrl-C to stop",
+        keepalives.len()
+    );
+    signal::install();
+
+    let start = Instant::now();
+    let mut next_sends: Vec<Instant> = keepalives
+        .iter()
+        .map(|(_, interval)| start + *interval)
+        .collect();
+    let mut sent = 0;
+    while !signal::interrupted() && deadline.is_none_or(|deadline| Instant::now() < deadline) {
+        let now = Instant::now();
+        for ((target, interval), next_send) in keepalives.iter().zip(next_sends.iter_mut()) {
+            if now < *next_send {
+                continue;
+            }
+            *next_send = now + *interval;
+            let mac_display = format_target_mac(target, mac_case);
+            let packet = plan.packet(&target.mac);
+            for network in plan.destinations(target) {
+                for port in &plan.ports {
+                    let broadcast = plan.broadcast(&network);
+                    let socket = sockets.get(&network);
+                    match batcher.send(socket, &packet, &network, broadcast, *port).result {
+                        Ok(_) => {
+                            sent += 1;
+                            if verbose_mode {
+                                println!("Keepalive sent to {}, network: {}", mac_display, network);
+                            }
+                        }
+                        Err(err) => println!(
+                            "Error: {}, original MAC address: {}, network: {}",
+                            err, mac_display, network
+                        ),
+                    }
+                }
+            }
+        }
+        let next_send = next_sends.iter().min().unwrap();
+        thread::sleep(
+            next_send
+                .saturating_duration_since(Instant::now())
+                .min(Duration::from_millis(100)),
+        );
+    }
+    println!(
+        "Stopped keeping devices awake after {:.0?}, {} sent",
+        start.elapsed(),
+        plural(sent, "keepalive")
+    );
+}
+
+#[cfg(feature = "http")]
+fn wake_over_http(
+    endpoint: &str,
+    matches: &clap::ArgMatches,
+    targets: &[Target],
+    mac_case: MacCase,
+    verbose_mode: bool,
+) -> ExitCode {
+    let headers = matches
+        .get_many::<String>("header")
+        .unwrap_or_default()
+        .map(|header| http::parse_header(header))
+        .collect::<Result<Vec<_>, _>>();
+    let headers = match headers {
+        Ok(v) => v,
+        Err(err) => {
+            println!("Error: {}", err);
+            return ExitCode::InvalidArguments;
+        }
+    };
+    let request = http::HttpWake {
+        endpoint: endpoint.to_string(),
+        body: matches.get_one::<String>("http_body").unwrap().clone(),
+        content_type: matches
+            .get_one::<String>("http_content_type")
+            .unwrap()
+            .clone(),
+        headers,
+    };
+
+    let mut failed = 0;
+    for target in targets {
+        let mac_display = format_target_mac(target, mac_case);
+        match request.send(&mac_display) {
+            Ok(status) => {
+                if verbose_mode {
+                    println!(
+                        "Sent wake request for {} to {} (HTTP {})",
+                        mac_display, endpoint, status
+                    );
+                }
+            }
+            Err(err) => {
+                println!(
+                    "Error: {}, original MAC address: {}, endpoint: {}",
+                    err, mac_display, endpoint
+                );
+                failed += 1;
+            }
+        }
+    }
+    ExitCode::from_sends(targets.len(), failed)
+}
+
+#[cfg(not(feature = "http"))]
+fn wake_over_http(
+    _endpoint: &str,
+    _matches: &clap::ArgMatches,
+    _targets: &[Target],
+    _mac_case: MacCase,
+    _verbose_mode: bool,
+) -> ExitCode {
+    println!("Error: --http-endpoint is not available in this build, rebuild with --features http");
+    ExitCode::InvalidArguments
+}
+
+#[cfg(feature = "mqtt")]
+fn wake_over_mqtt(
+    broker: &str,
+    matches: &clap::ArgMatches,
+    targets: &[Target],
+    mac_case: MacCase,
+    verbose_mode: bool,
+) -> ExitCode {
+    let broker = match mqtt::parse_broker(broker) {
+        Ok(v) => v,
+        Err(err) => {
+            println!("Error: {}", err);
+            return ExitCode::InvalidArguments;
+        }
+    };
+    let login = mqtt::Login {
+        username: matches.get_one::<String>("mqtt_username").cloned(),
+        password: matches
+            .get_one::<String>("mqtt_password")
+            .cloned()
+            .or_else(|| std::env::var("WOL_MQTT_PASSWORD").ok()),
+        ca_file: matches.get_one::<PathBuf>("mqtt_ca_file").cloned(),
+    };
+    let topic = matches.get_one::<String>("mqtt_topic").unwrap();
+    let template = matches.get_one::<String>("mqtt_payload").unwrap();
+    let password = read_password(matches);
+
+    let mut session = match mqtt::Session::connect(&broker, &login) {
+        Ok(v) => v,
+        Err(err) => {
+            println!("Error: {}, broker: {}:{}", err, broker.host, broker.port);
+            return ExitCode::TotalFailure;
+        }
+    };
+    let mut failed = 0;
+    for target in targets {
+        let mac_display = format_target_mac(target, mac_case);
+        let payload = if matches.get_flag("mqtt_packet") {
+            match &password {
+                Some(password) => build_magic_packet_with_password(&target.mac, password),
+                None => build_magic_packet(&target.mac),
+            }
+        } else {
+            template.replace("{mac}", &mac_display).into_bytes()
+        };
+        match session.publish(topic, &payload) {
+            Ok(()) => {
+                if verbose_mode {
+                    println!("Published a wake message for {} on {}", mac_display, topic);
+                }
+            }
+            Err(err) => {
+                println!(
+                    "Error: {}, original MAC address: {}, topic: {}",
+                    err, mac_display, topic
+                );
+                failed += 1;
+            }
+        }
+    }
+    session.disconnect();
+    ExitCode::from_sends(targets.len(), failed)
+}
+
+#[cfg(not(feature = "mqtt"))]
+fn wake_over_mqtt(
+    _broker: &str,
+    _matches: &clap::ArgMatches,
+    _targets: &[Target],
+    _mac_case: MacCase,
+    _verbose_mode: bool,
+) -> ExitCode {
+    println!("Error: --mqtt-broker is not available in this build, rebuild with --features mqtt");
+    ExitCode::InvalidArguments
+}
+
+// --version --json, for deployment scripts that check which build is installed
+fn version_json() -> String {
+    let features: Vec<String> = [
+        ("http", cfg!(feature = "http")),
+        ("mdns", cfg!(feature = "mdns")),
+        ("mqtt", cfg!(feature = "mqtt")),
+        ("serde", cfg!(feature = "serde")),
+        ("tokio", cfg!(feature = "tokio")),
+    ]
+    .iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(name, _)| json_string(name))
+    .collect();
+    format!(
+        "{{\"name\":\"wol\",\"version\":{},\"features\":[{}]}}",
+        json_string(cli().get_version().unwrap_or_default()),
+        features.join(",")
+    )
+}
+
+fn cli() -> Command {
+    Command::new("Wake on LAN")
+        .version("1.0")
+        .author("sxul07 <sxul07@hotmail.com>")
+        .about("Wake up devices on the network")
+        // --version is handled in main so that --json can change its output
+        .disable_version_flag(true)
+        .arg(
+            Arg::new("version")
+                .short('V')
+                .long("version")
+                .action(clap::ArgAction::SetTrue)
+                .help("Print version")
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .action(clap::ArgAction::SetTrue)
+                .requires("version")
+                .help("With --version, prints the name, version and compiled in features as JSON")
+        )
+        .arg(
+            Arg::new("mac_address")
+                .value_name("MAC_ADDRESS")
+                .help("Target MAC address, e.g. 00:11:22:33:44:55, 00-11-22-33-44-55, 0011.2233.4455 or 001122334455, or a range of sequential ones, e.g. 00:11:22:33:44:00-00:11:22:33:44:0f or 00:11:22:33:44:00+16")
+                .num_args(1..)
+                .required_unless_present_any(["file", "from_dhcp_leases", "mdns_discover", "check_config", "normalize_file", "version", "resolve_only", "server"]),
+        )
+        .arg(
+            Arg::new("file")
+                .short('f')
+                .long("file")
+                .value_name("FILE")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Reads target MAC addresses from a file, one per line. MAC addresses given as arguments are added to them, unless --file-only. Lines starting with #, // or ; are ignored. A line can be followed by the device's IP address, e.g. 00:11:22:33:44:55 192.168.1.20, to send to it by unicast."),
+        )
+        .arg(
+            Arg::new("file_format")
+                .long("file-format")
+                .value_name("FORMAT")
+                .visible_alias("input-format")
+                .value_parser(["auto", "plain", "dnsmasq", "isc", "hex-lines"])
+                .default_value("auto")
+                .help("The format of the --file input: auto detects lease files by their content and reads everything else as plain target lines, dnsmasq and isc force a lease file format, hex-lines reads a whole magic packet in hex per line and sends it as it is")
+        )
+        .arg(
+            Arg::new("file_only")
+                .long("file-only")
+                .action(clap::ArgAction::SetTrue)
+                .requires("file")
+                .help("Only wake the targets in the --file input, the MAC addresses given as arguments are ignored"),
+        )
+        .arg(
+            Arg::new("allow_exec")
+                .long("allow-exec")
+                .action(clap::ArgAction::SetTrue)
+                .help("Run the !COMMAND lines of the --file input and read targets from their output. Only use with files you trust"),
+        )
+        .arg(
+            Arg::new("from_dhcp_leases")
+                .long("from-dhcp-leases")
+                .value_name("PATH")
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with("file")
+                .help("Reads the targets from a DHCP lease file, every lease is woken by unicast to its IP address. MAC addresses given as arguments are woken too")
+        )
+        .arg(
+            Arg::new("lease_format")
+                .long("lease-format")
+                .value_name("FORMAT")
+                .value_parser(["dnsmasq", "isc"])
+                .default_value("dnsmasq")
+                .requires("from_dhcp_leases")
+                .help("The format of the --from-dhcp-leases file: dnsmasq (dnsmasq.leases) or isc (ISC dhcpd's dhcpd.leases)")
+        )
+        .arg(
+            Arg::new("fail_fast")
+                .long("fail-fast")
+                .visible_alias("strict")
+                .action(clap::ArgAction::SetTrue)
+                .help("Stops before sending anything when a MAC address, file line or lease is invalid, instead of skipping it. Sends on an interface without a carrier count as failed")
+        )
+        .arg(
+            Arg::new("net")
+                .short('n')
+                .long("net")
+                .value_name("NET")
+                .num_args(1)
+                .action(clap::ArgAction::Append)
+                .help("Specify the network address to send the broadcast, use CIDR notation, e.g. 192.168.1.0/24, or auto for the detected networks. Can be repeated"),
+        )
+        .arg(
+            Arg::new("net_file")
+                .long("net-file")
+                .value_name("PATH")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Reads networks to broadcast on from PATH, one per line in the --net form, in addition to --net. Blank lines and comments are skipped")
+        )
+        .arg(
+            Arg::new("network_from_host")
+                .long("network-from-host")
+                .value_name("IP")
+                .value_parser(clap::value_parser!(Ipv4Addr))
+                .conflicts_with_all(["net", "net_file", "host", "interface", "interface_regex"])
+                .help("Broadcasts on the network that contains IP, with the prefix length from --prefix, e.g. 192.168.50.40 broadcasts to 192.168.50.255")
+        )
+        .arg(
+            Arg::new("prefix")
+                .long("prefix")
+                .value_name("LENGTH")
+                .value_parser(clap::value_parser!(u8).range(0..=32))
+                .default_value("24")
+                .requires("network_from_host")
+                .help("The prefix length of the --network-from-host network")
+        )
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .help("Enables verbose mode")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("interface")
+                .short('i')
+                .long("interface")
+                .value_name("NAME")
+                .action(clap::ArgAction::Append)
+                .conflicts_with_all(["net", "net_file", "host"])
+                .help("Only broadcasts on the networks of the interface NAME, e.g. eth0. Can be repeated")
+        )
+        .arg(
+            Arg::new("interface_regex")
+                .long("interface-regex")
+                .value_name("PATTERN")
+                .value_parser(Regex::new)
+                .action(clap::ArgAction::Append)
+                .conflicts_with_all(["net", "net_file", "host"])
+                .help("Only broadcasts on the networks of interfaces whose name matches the regular expression PATTERN, e.g. ^eth[0-9]$. Combined with --interface")
+        )
+        .arg(
+            Arg::new("interface_ip")
+                .long("interface-ip")
+                .value_name("IP")
+                .value_parser(clap::value_parser!(Ipv4Addr))
+                .conflicts_with_all(["net", "net_file", "host", "network_from_host"])
+                .help("Only broadcasts on the network of the local address IP, for an interface with several addresses. Combined with --interface")
+        )
+        .arg(
+            Arg::new("all_interfaces")
+                .long("all-interfaces")
+                .action(clap::ArgAction::SetTrue)
+                .help("Also broadcast on interfaces that are down, and on loopback and link-local addresses, which are skipped by default"),
+        )
+        .arg(
+            Arg::new("host")
+                .long("host")
+                .value_name("HOST")
+                .conflicts_with_all(["net", "net_file"])
+                .help("Sends the magic packet to HOST by unicast instead of broadcasting, HOST is an IPv4 address or a hostname")
+        )
+        .arg(
+            Arg::new("no_broadcast_fallback")
+                .long("no-broadcast-fallback")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "net",
+                    "net_file",
+                    "interface",
+                    "interface_regex",
+                    "network_from_host",
+                ])
+                .help("Only sends by unicast, to --host or the IP addresses in --file, and never enables broadcast on the socket. Targets without an IP address are an error")
+        )
+        .arg(
+            Arg::new("http_endpoint")
+                .long("http-endpoint")
+                .value_name("URL")
+                .conflicts_with_all(["host", "net", "net_file"])
+                .help("POSTs each MAC address to a router's WOL web API at URL instead of sending magic packets, requires the http feature")
+        )
+        .arg(
+            Arg::new("http_body")
+                .long("http-body")
+                .value_name("BODY")
+                .default_value("{\"mac\":\"{mac}\"}")
+                .help("Request body for --http-endpoint, {mac} is replaced by the MAC address")
+        )
+        .arg(
+            Arg::new("http_content_type")
+                .long("http-content-type")
+                .value_name("TYPE")
+                .default_value("application/json")
+                .help("Content-Type of the --http-endpoint request, e.g. application/x-www-form-urlencoded for a form body")
+        )
+        .arg(
+            Arg::new("header")
+                .long("header")
+                .value_name("HEADER")
+                .action(clap::ArgAction::Append)
+                .help("Adds a \"Name: value\" header to the --http-endpoint request, e.g. for authorization. Can be repeated")
+        )
+        .arg(
+            Arg::new("mqtt_broker")
+                .long("mqtt-broker")
+                .value_name("URL")
+                .requires("mqtt_topic")
+                .conflicts_with_all(["host", "net", "net_file", "http_endpoint"])
+                .help("Publishes a wake message for each MAC address to the MQTT broker at URL, mqtt://HOST[:PORT] or mqtts://HOST[:PORT] for TLS, instead of sending magic packets, requires the mqtt feature")
+        )
+        .arg(
+            Arg::new("mqtt_topic")
+                .long("mqtt-topic")
+                .value_name("TOPIC")
+                .requires("mqtt_broker")
+                .help("Topic the --mqtt-broker wake messages are published on")
+        )
+        .arg(
+            Arg::new("mqtt_payload")
+                .long("mqtt-payload")
+                .value_name("TEMPLATE")
+                .default_value("{mac}")
+                .conflicts_with("mqtt_packet")
+                .help("Message published on --mqtt-topic, {mac} is replaced by the MAC address")
+        )
+        .arg(
+            Arg::new("mqtt_packet")
+                .long("mqtt-packet")
+                .action(clap::ArgAction::SetTrue)
+                .requires("mqtt_broker")
+                .help("Publishes the magic packet itself instead of --mqtt-payload")
+        )
+        .arg(
+            Arg::new("mqtt_username")
+                .long("mqtt-username")
+                .value_name("NAME")
+                .requires("mqtt_broker")
+                .help("Username to log in to the --mqtt-broker with")
+        )
+        .arg(
+            Arg::new("mqtt_password")
+                .long("mqtt-password")
+                .value_name("PASSWORD")
+                .requires("mqtt_broker")
+                .help("Password to log in to the --mqtt-broker with, read from WOL_MQTT_PASSWORD when not given")
+        )
+        .arg(
+            Arg::new("mqtt_ca_file")
+                .long("mqtt-ca-file")
+                .value_name("FILE")
+                .value_parser(clap::value_parser!(PathBuf))
+                .requires("mqtt_broker")
+                .help("Trusts the certificate authorities in the PEM FILE instead of the public ones for an mqtts:// broker, e.g. for a self-signed certificate")
+        )
+        .arg(
+            Arg::new("port")
+                .short('p')
+                .long("port")
+                .value_name("PORT")
+                .value_parser(clap::value_parser!(u16).range(1..))
+                .action(clap::ArgAction::Append)
+                .help("UDP port to send the magic packet to, can be repeated to send to several ports [default: 9]")
+        )
+        .arg(
+            Arg::new("batch_size")
+                .long("batch-size")
+                .value_name("N")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .help("Pauses after every N packets sent, see --batch-pause")
+        )
+        .arg(
+            Arg::new("batch_pause")
+                .long("batch-pause")
+                .value_name("DURATION")
+                .value_parser(duration::parse_millis_or_duration)
+                .default_value("1000")
+                .help("How long to pause between batches, e.g. 2s, a bare number is milliseconds")
+        )
+        .arg(
+            Arg::new("repeat")
+                .long("repeat")
+                .value_name("N")
+                .value_parser(clap::value_parser!(u32).range(1..))
+                .default_value("1")
+                .help("Sends every packet N times")
+        )
+        .arg(
+            Arg::new("burst")
+                .long("burst")
+                .value_name("N")
+                .value_parser(clap::value_parser!(u32).range(1..))
+                .default_value("1")
+                .help("Send every packet N times back to back, for WiFi NICs in power save that only catch one packet of a burst"),
+        )
+        .arg(
+            Arg::new("delay")
+                .long("delay")
+                .value_name("DURATION")
+                .value_parser(duration::parse_millis_or_duration)
+                .default_value("100ms")
+                .help("How long to pause between repeats, e.g. 500ms or 2s, a bare number is milliseconds")
+        )
+        .arg(
+            Arg::new("hold_open")
+                .long("hold-open")
+                .value_name("DURATION")
+                .value_parser(duration::parse_millis_or_duration)
+                .help("Keeps the socket open for DURATION after the last packet is sent, e.g. 500 or 2s, a bare number is milliseconds, for network adapters that lose packets when it closes right away")
+        )
+        .arg(
+            Arg::new("backoff")
+                .long("backoff")
+                .action(clap::ArgAction::SetTrue)
+                .help("Doubles the pause after every repeat, starting at --delay")
+        )
+        .arg(
+            Arg::new("max_delay")
+                .long("max-delay")
+                .value_name("DURATION")
+                .value_parser(duration::parse_millis_or_duration)
+                .requires("backoff")
+                .help("The longest pause --backoff grows to")
+        )
+        .arg(
+            Arg::new("broadcast_only_reachable")
+                .long("broadcast-only-reachable")
+                .action(clap::ArgAction::SetTrue)
+                .help("Ping the broadcast address of every network first and skip the networks where no host answers, to catch a wrong interface or an isolated VLAN. Networks that can't be pinged are still used")
+        )
+        .arg(
+            Arg::new("retry_networks")
+                .long("retry-networks")
+                .value_name("N")
+                .value_parser(clap::value_parser!(u32))
+                .default_value("0")
+                .help("After all packets are sent, resend only on the networks that failed, up to N times")
+        )
+        .arg(
+            Arg::new("retry_on_down")
+                .long("retry-on-down")
+                .value_name("HOST[:PORT]")
+                .help("Before every --repeat and --retry-networks resend, try a TCP connection to HOST on PORT, or on every --probe-port, and stop resending once it answers")
+        )
+        .arg(
+            Arg::new("probe_port")
+                .long("probe-port")
+                .value_name("PORT")
+                .value_parser(clap::value_parser!(u16).range(1..))
+                .action(clap::ArgAction::Append)
+                .requires("retry_on_down")
+                .help("The port --retry-on-down tries when HOST has no :PORT, can be repeated, the host is up once any of them answers [default: 22]")
+        )
+        .arg(
+            Arg::new("wake_and_ssh")
+                .long("wake-and-ssh")
+                .value_name("[USER@]HOST[:PORT]")
+                .help("After sending, wait until HOST accepts connections on PORT, 22 by default, then run ssh, or the --then command, and exit with its exit code")
+        )
+        .arg(
+            Arg::new("then")
+                .long("then")
+                .value_name("CMD")
+                .requires("wake_and_ssh")
+                .help("The command --wake-and-ssh runs through the shell once the host is up, instead of ssh")
+        )
+        .arg(
+            Arg::new("wait_timeout")
+                .long("wait-timeout")
+                .value_name("DURATION")
+                .value_parser(duration::parse_duration)
+                .default_value("2m")
+                .help("How long --wake-and-ssh waits for the host to come up, e.g. 90s or 5m")
+        )
+        .arg(
+            Arg::new("at")
+                .long("at")
+                .value_name("TIME")
+                .value_parser(schedule::parse_wake_time)
+                .help("Waits until TIME before sending: HH:MM (today, or tomorrow if already past), an ISO timestamp such as 2024-05-01T07:00, or a relative time such as +30m")
+        )
+        .arg(
+            Arg::new("min_interval")
+                .long("min-interval")
+                .value_name("DURATION")
+                .value_parser(duration::parse_secs_or_duration)
+                .help("Skips the targets that were woken less than DURATION ago, e.g. 10m, a bare number is seconds. The wake times are kept in --state-file")
+        )
+        .arg(
+            Arg::new("state_file")
+                .long("state-file")
+                .value_name("PATH")
+                .value_parser(clap::value_parser!(PathBuf))
+                .requires("min_interval")
+                .help("Where --min-interval keeps the last wake time of every MAC address, by default $XDG_STATE_HOME/wol/last-wakes")
+        )
+        .arg(
+            Arg::new("watch")
+                .long("watch")
+                .value_name("PATH")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Keeps running until Ctrl-C and wakes the targets every time the file or directory PATH is created, modified or removed")
+        )
+        .arg(
+            Arg::new("server")
+                .long("server")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["mac_address", "file", "from_dhcp_leases", "watch", "client"])
+                .help("Keeps running until Ctrl-C and wakes the targets that --client sends to the --server-socket, one target line per line, reusing one socket and the networks found at start")
+        )
+        .arg(
+            Arg::new("client")
+                .long("client")
+                .action(clap::ArgAction::SetTrue)
+                .requires("mac_address")
+                .help("Asks the --server listening on the --server-socket to wake the MAC addresses and prints its replies")
+        )
+        .arg(
+            Arg::new("server_socket")
+                .long("server-socket")
+                .value_name("PATH")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("The Unix socket of --server and --client, $XDG_RUNTIME_DIR/wol.sock or wol.sock in the temp directory by default")
+        )
+        .arg(
+            Arg::new("max_runtime")
+                .long("max-runtime")
+                .value_name("DURATION")
+                .value_parser(duration::parse_duration)
+                .help("Exit --watch, --server and the keepalive loop cleanly after DURATION, e.g. 2h or 30m"),
+        )
+        .arg(
+            Arg::new("watch_debounce")
+                .long("watch-debounce")
+                .value_name("DURATION")
+                .value_parser(duration::parse_duration)
+                .default_value("500ms")
+                .requires("watch")
+                .help("How long --watch waits for the changes to PATH to settle before waking")
+        )
+        .arg(
+            Arg::new("count")
+                .long("count")
+                .value_name("N")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .help("Only use the first N targets, after reading, filtering and --randomize-order"),
+        )
+        .arg(
+            Arg::new("oui_prefix")
+                .long("oui-prefix")
+                .value_name("AA:BB:CC")
+                .action(clap::ArgAction::Append)
+                .help("Only wake the targets whose MAC address starts with the vendor prefix AA:BB:CC, e.g. to wake the machines of one vendor from a mixed list. Can be repeated")
+        )
+        .arg(
+            Arg::new("list_vendors")
+                .long("list-vendors")
+                .action(clap::ArgAction::SetTrue)
+                .help("Prints how many targets have every vendor prefix (OUI), the most common first, and exits without sending")
+        )
+        .arg(
+            Arg::new("randomize_order")
+                .long("randomize-order")
+                .help("Shuffles the target MAC addresses before sending")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_name("SEED")
+                .value_parser(clap::value_parser!(u64))
+                .requires("randomize_order")
+                .help("Seed for --randomize-order, the same seed always gives the same order")
+        )
+        .arg(
+            Arg::new("raw_payload")
+                .long("raw-payload")
+                .value_name("FILE")
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with_all(["password", "password_file"])
+                .help("Send the bytes of FILE verbatim instead of building a magic packet from the MAC address, for nonstandard wake frames"),
+        )
+        .arg(
+            Arg::new("payload_prefix")
+                .long("payload-prefix")
+                .value_name("HEX")
+                .conflicts_with("raw_payload")
+                .help("Send the bytes HEX, e.g. 574f4c, in front of the magic packet, for relays that pick packets by a leading token. Standard receivers ignore such packets"),
+        )
+        .arg(
+            Arg::new("simulate_loss")
+                .long("simulate-loss")
+                .value_name("PERCENT")
+                .value_parser(clap::value_parser!(u8).range(0..=100))
+                .hide(true)
+                .help("Testing aid: drop PERCENT of the sends instead of sending them, to check that --repeat and --retry-networks recover"),
+        )
+        .arg(
+            Arg::new("password")
+                .long("password")
+                .value_name("PASSWORD")
+                .num_args(1)
+                .conflicts_with("password_file")
+                .help("Append a SecureOn password to every packet, 6 hex bytes like DE:AD:BE:EF:00:01 or 4 bytes like 192.168.1.1. Also read from $WOL_PASSWORD"),
+        )
+        .arg(
+            Arg::new("password_file")
+                .long("password-file")
+                .value_name("PATH")
+                .num_args(1)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Read the SecureOn password from the first line of PATH, so it doesn't show up in shell history"),
+        )
+        .arg(
+            Arg::new("echo_targets")
+                .long("echo-targets")
+                .action(clap::ArgAction::SetTrue)
+                .help("Print the final list of targets, with their IP address, name and keepalive, then exit without sending"),
+        )
+        .arg(
+            Arg::new("write_pcap")
+                .long("write-pcap")
+                .value_name("FILE")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Write every packet that is sent to FILE in pcap format, wrapped in Ethernet, IPv4 and UDP headers, to inspect it offline or attach it to a bug report"),
+        )
+        .arg(
+            Arg::new("confirm_each")
+                .long("confirm-each")
+                .action(clap::ArgAction::SetTrue)
+                .help("Before every target is sent to, print it and ask \"Wake this one? [y/N/q]\", q skips all the remaining targets. Needs a terminal unless --yes is given")
+        )
+        .arg(
+            Arg::new("yes")
+                .long("yes")
+                .short('y')
+                .action(clap::ArgAction::SetTrue)
+                .help("Answer yes to every --confirm-each question, for scripts")
+        )
+        .arg(
+            Arg::new("keep_arp")
+                .long("keep-arp")
+                .action(clap::ArgAction::SetTrue)
+                .help("Don't remove the ARP entries added for unicast targets after sending, for debugging"),
+        )
+        .arg(
+            Arg::new("arp_verify")
+                .long("arp-verify")
+                .action(clap::ArgAction::SetTrue)
+                .help("Before sending, look up every target in the ARP cache and warn when it was last seen outside the networks it is sent to"),
+        )
+        .arg(
+            Arg::new("dump_config")
+                .long("dump-config")
+                .action(clap::ArgAction::SetTrue)
+                .help("Print the effective settings as JSON, after every flag, file and environment variable is resolved, then exit without sending"),
+        )
+        .arg(
+            Arg::new("explain")
+                .long("explain")
+                .action(clap::ArgAction::SetTrue)
+                .help("Describes what will be sent in plain English before sending")
+        )
+        .arg(
+            Arg::new("dry_run")
+                .long("dry-run")
+                .action(clap::ArgAction::SetTrue)
+                .help("Lists every packet that would be sent, then exits without sending")
+        )
+        .arg(
+            Arg::new("resolve_only")
+                .long("resolve-only")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["file", "from_dhcp_leases"])
+                .help("Takes IP addresses or hostnames instead of MAC addresses, with --host or as arguments, prints the MAC address of each from the ARP cache and exits without sending")
+        )
+        .arg(
+            Arg::new("validate_only_unknown")
+                .long("validate-only-unknown")
+                .value_name("INPUT")
+                .value_parser(clap::value_parser!(PathBuf))
+                .requires("file")
+                .help("Prints \"unknown MAC\" for every MAC address in the file INPUT that --file doesn't list, and exits without sending. Exits with 1 if there is any")
+        )
+        .arg(
+            Arg::new("unreferenced")
+                .long("unreferenced")
+                .action(clap::ArgAction::SetTrue)
+                .requires("validate_only_unknown")
+                .help("Also prints \"unreferenced MAC NAME\" for every --file entry that the --validate-only-unknown INPUT doesn't hold")
+        )
+        .arg(
+            Arg::new("dry_run_packet_only")
+                .long("dry-run-packet-only")
+                .action(clap::ArgAction::SetTrue)
+                .help("Prints every target's MAC address and the hex of the packet it would get, then exits without looking up networks or opening a socket")
+        )
+        .arg(
+            Arg::new("normalize_file")
+                .long("normalize-file")
+                .value_name("FILE")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Rewrites every valid MAC address in FILE in the canonical 00:11:22:33:44:55 form, lowercase with --lowercase, and exits without sending. Comments, blank lines and invalid lines are kept as they are")
+        )
+        .arg(
+            Arg::new("check_config")
+                .long("check-config")
+                .action(clap::ArgAction::SetTrue)
+                .help("Validates the input file, MAC addresses, --host, interfaces and networks without sending anything, and prints a report. Exits with 0 only if everything is valid")
+        )
+        .arg(
+            Arg::new("mdns_discover")
+                .long("mdns-discover")
+                .help("Lists devices found via mDNS with their MAC address and exits, requires the mdns feature")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("discover_timeout")
+                .long("discover-timeout")
+                .value_name("DURATION")
+                .value_parser(duration::parse_secs_or_duration)
+                .default_value("3")
+                .help("How long --mdns-discover listens for devices, e.g. 500ms, a bare number is seconds")
+        )
+        .arg(
+            Arg::new("compact")
+                .long("compact")
+                .help("Prints a single summary line instead of a line per target")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("verbose")
+        )
+        .arg(
+            Arg::new("group_by_network")
+                .long("group-by-network")
+                .action(clap::ArgAction::SetTrue)
+                .help("Print the results grouped by network, with every target sent to on it, instead of by target"),
+        )
+        .arg(
+            Arg::new("timing")
+                .long("timing")
+                .help("Prints how long each send took, and min/max/avg over the whole run")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("on_success")
+                .long("on-success")
+                .value_name("CMD")
+                .help("Run CMD through the shell for every target that got its packet, with $WOL_MAC, $WOL_NAME, $WOL_RESULT, $WOL_SENT and $WOL_FAILED set"),
+        )
+        .arg(
+            Arg::new("on_failure")
+                .long("on-failure")
+                .value_name("CMD")
+                .help("Run CMD through the shell for every target whose packets all failed, with the same variables as --on-success"),
+        )
+        .arg(
+            Arg::new("stats_json")
+                .long("stats-json")
+                .action(clap::ArgAction::SetTrue)
+                .help("Prints a JSON object with the totals of the run and per target counts at the end, for metrics collection")
+        )
+        .arg(
+            Arg::new("template")
+                .long("template")
+                .value_name("TEMPLATE")
+                .value_parser(clap::value_parser!(Template))
+                .help("Prints this line for every send, with {mac}, {name}, {network}, {broadcast}, {port}, {result}, {time} and {run_id} substituted, e.g. \"{mac} {broadcast}:{port} {result}\"")
+        )
+        .arg(
+            Arg::new("lowercase")
+                .long("lowercase")
+                .help("Prints MAC addresses in lowercase instead of uppercase, like --mac-case lower")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("mac_case")
+                .long("mac-case")
+                .value_name("CASE")
+                .value_parser(["upper", "lower", "as-is"])
+                .conflicts_with("lowercase")
+                .help("How MAC addresses are printed: upper, the default, lower, or as-is to print every target the way it was written in the arguments or the --file, e.g. 0011.2233.4455")
+        )
+}
+
+// --server-socket, or where --server listens by default
+fn server_socket(matches: &clap::ArgMatches) -> PathBuf {
+    matches
+        .get_one::<PathBuf>("server_socket")
+        .cloned()
+        .unwrap_or_else(server::default_path)
+}
+
+// --client, hand the MAC addresses to a running --server and print its
+// replies, one per MAC address. Empty and comment lines aren't sent, the
+// server doesn't answer them
+fn send_to_server(matches: &clap::ArgMatches) -> ExitCode {
+    let socket_path = server_socket(matches);
+    let lines: Vec<String> = matches
+        .get_many::<String>("mac_address")
+        .unwrap_or_default()
+        .filter(|line| !is_skipped_line(line))
+        .cloned()
+        .collect();
+    let replies = match server::send(&socket_path, &lines) {
+        Ok(v) => v,
+        Err(err) => {
+            println!("Error: failed to reach the server at {:?}: {}", socket_path, err);
+            return ExitCode::Io;
+        }
+    };
+    for reply in &replies {
+        println!("{}", reply);
+    }
+    // a line the server didn't answer counts as failed
+    let sent = replies.iter().filter(|reply| reply.starts_with("ok ")).count();
+    ExitCode::from_sends(lines.len(), lines.len() - sent)
+}
+
+// --resolve-only, print the MAC address of --host and of every argument, an
+// IP address or hostname, from the ARP cache. The addresses that aren't in it
+// are probed once to make the kernel ask for them
+fn resolve_only(matches: &clap::ArgMatches, mac_case: MacCase) -> ExitCode {
+    let hosts: Vec<&String> = matches
+        .get_one::<String>("host")
+        .into_iter()
+        .chain(matches.get_many::<String>("mac_address").unwrap_or_default())
+        .collect();
+    let mut failed = 0;
+    let mut resolved = Vec::new();
+    for host in &hosts {
+        match resolve_host(host) {
+            Ok(ip) => resolved.push((host, ip)),
+            Err(err) => {
+                println!("Error: failed to resolve host {}: {}", host, err);
+                failed += 1;
+            }
+        }
+    }
+
+    let read_table = || {
+        arp::read_arp_table().unwrap_or_else(|err| {
+            println!("Error: can't read the ARP cache: {}", err);
+            ExitCode::Io.exit();
+        })
+    };
+    let mut table = read_table();
+    let missing: Vec<Ipv4Addr> = resolved
+        .iter()
+        .map(|(_, ip)| *ip)
+        .filter(|ip| arp::lookup(&table, *ip).is_none())
+        .collect();
+    if !missing.is_empty() {
+        for ip in &missing {
+            if let Err(err) = arp::probe(*ip) {
+                println!("Warning: failed to probe {}: {}", ip, err);
+            }
+        }
+        thread::sleep(PROBE_TIMEOUT);
+        table = read_table();
+    }
+
+    for (host, ip) in resolved {
+        let label = if host.parse::<Ipv4Addr>().is_ok() {
+            ip.to_string()
+        } else {
+            format!("{} ({})", host, ip)
+        };
+        match arp::lookup(&table, ip) {
+            Some(entry) => println!("{} -> {}", label, format_mac(&entry.mac, mac_case)),
+            None => {
+                println!("Error: {} is not in the ARP cache, it didn't answer", label);
+                failed += 1;
+            }
+        }
+    }
+    ExitCode::from_sends(hosts.len(), failed)
+}
+
+// --validate-only-unknown, print every MAC address of `input_path` that the
+// --file doesn't list, and with --unreferenced every --file entry that
+// `input_path` doesn't hold. Exits with 1 when there is any, like diff
+fn unknown_macs(matches: &clap::ArgMatches, input_path: &Path, mac_case: MacCase) -> ExitCode {
+    let file_path = matches.get_one::<PathBuf>("file").unwrap();
+    let hex_lines = matches.get_one::<String>("file_format").unwrap() == "hex-lines";
+    let mut texts = MacTexts::new();
+    let (input, _, _) = read_targets_from_file(input_path, false, false, &mut texts);
+    let known = match file_format(matches, file_path, false) {
+        _ if hex_lines => read_packet_lines(file_path, false).0,
+        Some(format) => read_leases(file_path, format, false, &mut texts).0,
+        None => read_targets_from_file(file_path, false, false, &mut texts).0,
+    };
+    let mac_case = mac_case.with_texts(&texts);
+
+    let mut differences = 0;
+    let mut seen = HashSet::new();
+    for target in &input {
+        if seen.insert(target.mac) && !known.iter().any(|entry| entry.mac == target.mac) {
+            println!("unknown {}", format_target_mac(target, mac_case));
+            differences += 1;
+        }
+    }
+    if matches.get_flag("unreferenced") {
+        for entry in &known {
+            if !seen.contains(&entry.mac) {
+                let name = entry.name.as_deref().unwrap_or("-");
+                println!("unreferenced {} {}", format_target_mac(entry, mac_case), name);
+                differences += 1;
+            }
+        }
+    }
+
+    if differences == 0 {
+        ExitCode::Success
+    } else {
+        ExitCode::PartialFailure
+    }
+}
+
+// rewrite the MAC addresses of a targets file in the canonical form, leaving
+// everything else on the line as it is
+fn normalize_file(file_path: &PathBuf, mac_case: MacCase) -> ExitCode {
+    let content = match std::fs::read_to_string(file_path) {
+        Ok(v) => v,
+        Err(err) => {
+            println!("Error: failed to read {:?}: {}", file_path, err);
+            return ExitCode::Io;
+        }
+    };
+
+    let mut normalized = String::with_capacity(content.len());
+    let mut valid = 0;
+    let mut reformatted = 0;
+    let mut invalid = 0;
+    for (line_index, line) in content.split_inclusive('\n').enumerate() {
+        match parse_target_line(line) {
+            Ok(Some(TargetLine { targets, .. })) => {
+                valid += 1;
+                let indent = line.len() - line.trim_start().len();
+                let rest = &line[indent..];
+                let mac_end = indent + rest.find(char::is_whitespace).unwrap_or(rest.len());
+                // a range is kept as it is
+                if line[indent..mac_end].parse::<MacAddr>().is_err() {
+                    normalized.push_str(line);
+                    continue;
+                }
+                let mac_display = format_mac(&targets[0].0.mac, mac_case);
+                if line[indent..mac_end] != mac_display {
+                    reformatted += 1;
+                }
+                normalized.push_str(&line[..indent]);
+                normalized.push_str(&mac_display);
+                normalized.push_str(&line[mac_end..]);
+            }
+            Ok(None) => normalized.push_str(line),
+            Err(err) => {
+                println!("Warning: keeping line {} as it is: {}", line_index + 1, err);
+                invalid += 1;
+                normalized.push_str(line);
+            }
+        }
+    }
+
+    // write next to the file and rename, so a failure can't truncate it
+    let mut temp_path = file_path.clone().into_os_string();
+    temp_path.push(".tmp");
+    let written = std::fs::write(&temp_path, normalized)
+        .and_then(|_| std::fs::rename(&temp_path, file_path));
+    if let Err(err) = written {
+        let _ = std::fs::remove_file(&temp_path);
+        println!("Error: failed to write {:?}: {}", file_path, err);
+        return ExitCode::Io;
+    }
+
+    println!(
+        "Reformatted {} of {} MAC addresses in {:?}",
+        reformatted, valid, file_path
+    );
+    ExitCode::Success.with_skipped(invalid)
+}
+
+// the --check-config report, every problem is listed rather than stopping at
+// the first one
+fn check_config(matches: &clap::ArgMatches) -> ExitCode {
+    let mut problems = 0;
+    let mut report = |result: Result<String, String>| match result {
+        Ok(message) => println!("ok:    {}", message),
+        Err(message) => {
+            println!("error: {}", message);
+            problems += 1;
+        }
+    };
+
+    let file_path = matches.get_one::<PathBuf>("file");
+    let lease_format = file_path.and_then(|path| file_format(matches, path, false));
+    let hex_lines = matches.get_one::<String>("file_format").unwrap() == "hex-lines";
+    if let (Some(file_path), true) = (file_path, hex_lines) {
+        match std::fs::read_to_string(file_path) {
+            Ok(content) => {
+                let mut count = 0;
+                for (line_index, line) in content.lines().enumerate() {
+                    match parse_packet_line(line) {
+                        Ok(Some(_)) => count += 1,
+                        Ok(None) => {}
+                        Err(err) => report(Err(format!(
+                            "{:?} line {}: {}",
+                            file_path,
+                            line_index + 1,
+                            err
+                        ))),
+                    }
+                }
+                report(Ok(format!("{:?}: {} packet(s)", file_path, count)));
+            }
+            Err(err) => report(Err(format!("failed to read {:?}: {}", file_path, err))),
+        }
+    } else if let (Some(file_path), Some(format)) = (file_path, lease_format) {
+        match std::fs::read_to_string(file_path) {
+            Ok(content) => {
+                let (targets, skipped) = leases::parse_leases(&content, format);
+                for err in skipped {
+                    report(Err(format!("{:?}: {}", file_path, err)));
+                }
+                report(Ok(format!("{:?}: {} lease(s)", file_path, targets.len())));
+            }
+            Err(err) => report(Err(format!("failed to read {:?}: {}", file_path, err))),
+        }
+    } else if let Some(file_path) = file_path {
+        let mut count = 0;
+        let mut on_line = |path: &Path, line_number: usize, line: &str| {
+            match parse_target_line(line) {
+                Ok(Some(_)) => count += 1,
+                Ok(None) => {}
+                Err(err) => report(Err(format!("{:?} line {}: {}", path, line_number, err))),
+            }
+        };
+        let allow_exec = matches.get_flag("allow_exec");
+        let walked = walk_target_file(file_path, &mut Vec::new(), allow_exec, &mut on_line);
+        match walked {
+            Ok(_) => report(Ok(format!("{:?}: {} target(s)", file_path, count))),
+            Err(err) => report(Err(err)),
+        }
+    }
+
+    for mac_address in matches.get_many::<String>("mac_address").unwrap_or_default() {
+        report(match parse_mac_range(mac_address) {
+            Ok(v) if v.len() > 1 => Ok(format!(
+                "MAC address range {}, {} addresses",
+                mac_address,
+                v.len()
+            )),
+            Ok(_) => Ok(format!("MAC address {}", mac_address)),
+            Err(err) => Err(format!("{}, original MAC address: {}", err, mac_address)),
+        });
+    }
+
+    if let Some(host) = matches.get_one::<String>("host") {
+        report(match resolve_host(host) {
+            Ok(ip) => Ok(format!("host {} resolves to {}", host, ip)),
+            Err(err) => Err(format!("failed to resolve host {}: {}", host, err)),
+        });
+    }
+
+    let interfaces = match SystemInterfaces.interfaces() {
+        Ok(v) => v,
+        Err(err) => {
+            report(Err(format!("failed to list network interfaces: {}", err)));
+            Vec::new()
+        }
+    };
+    let local_networks: Vec<Ipv4Net> = interfaces.iter().map(InterfaceAddr::network).collect();
+    for name in matches.get_many::<String>("interface").unwrap_or_default() {
+        report(
+            match interfaces.iter().find(|if_addr| &if_addr.name == name) {
+                Some(if_addr) => Ok(format!("interface {} ({})", name, if_addr.network())),
+                None => Err(format!("interface {} does not exist", name)),
+            },
+        );
+    }
+    for pattern in matches.get_many::<Regex>("interface_regex").unwrap_or_default() {
+        let names: Vec<&str> = interfaces
+            .iter()
+            .filter(|if_addr| pattern.is_match(&if_addr.name))
+            .map(|if_addr| if_addr.name.as_str())
+            .collect();
+        report(if names.is_empty() {
+            Err(format!("--interface-regex {} matches no interface", pattern.as_str()))
+        } else {
+            Ok(format!("--interface-regex {} matches {}", pattern.as_str(), names.join(", ")))
+        });
+    }
+
+    if matches.contains_id("net") || matches.contains_id("net_file") {
+        for (net, location) in net_values(matches) {
+            if net == "auto" {
+                report(Ok(format!(
+                    "network auto, the networks of {}",
+                    plural(interfaces.len(), "interface")
+                )));
+                continue;
+            }
+            report(match parse_net(&net) {
+                // a network is routable when it overlaps a local one
+                Ok((ip_net, _))
+                    if local_networks.iter().any(|local_net| {
+                        local_net.contains(&ip_net.addr()) || ip_net.contains(&local_net.addr())
+                    }) =>
+                {
+                    Ok(format!("network {}", ip_net))
+                }
+                Ok((ip_net, _)) => Err(format!("network {} is not on a local interface", ip_net)),
+                Err(err) => match location {
+                    Some(location) => Err(format!("{}: {}", location, err)),
+                    None => Err(format!("{}, original network: {}", err, net)),
+                },
+            });
+        }
+    } else if !matches.contains_id("host") {
+        let names: Vec<&String> = matches
+            .get_many::<String>("interface")
+            .unwrap_or_default()
+            .collect();
+        let patterns: Vec<&Regex> = matches
+            .get_many::<Regex>("interface_regex")
+            .unwrap_or_default()
+            .collect();
+        let all_interfaces = matches.get_flag("all_interfaces");
+        let networks: Vec<String> = interfaces
+            .iter()
+            .filter(|if_addr| {
+                (names.is_empty() && patterns.is_empty())
+                    || names.contains(&&if_addr.name)
+                    || patterns.iter().any(|pattern| pattern.is_match(&if_addr.name))
+            })
+            .filter(|if_addr| all_interfaces || if_addr.is_broadcast_candidate())
+            .map(|if_addr| if_addr.network().to_string())
+            .collect();
+        report(if networks.is_empty() {
+            Err("no network to broadcast on was found".to_string())
+        } else {
+            Ok(format!("broadcasting on {}", networks.join(", ")))
+        });
+    }
+
+    if problems == 0 {
+        println!("Configuration is valid");
+        ExitCode::Success
+    } else {
+        println!("{} problem(s) found", problems);
+        ExitCode::InvalidArguments
+    }
+}
+
+// the networks of the local interfaces, narrowed down by --interface and
+// --interface-regex. Down, loopback and link-local addresses are skipped
+// unless --all-interfaces
+fn detect_networks(matches: &clap::ArgMatches) -> Vec<Ipv4Net> {
+    let interfaces = match SystemInterfaces.interfaces() {
+        Ok(v) => v,
+        Err(err) => {
+            println!("Error: failed to list network interfaces: {}", err);
+            ExitCode::Io.exit();
+        }
+    };
+    let mut interfaces = select_interfaces(
+        interfaces,
+        matches.get_many::<String>("interface"),
+        matches.get_many::<Regex>("interface_regex"),
+    );
+    // --interface-ip picks one of the addresses of an interface with aliases
+    if let Some(ip) = matches.get_one::<Ipv4Addr>("interface_ip") {
+        let addresses: Vec<String> = interfaces
+            .iter()
+            .map(|if_addr| format!("{} ({})", if_addr.ip, if_addr.name))
+            .collect();
+        interfaces.retain(|if_addr| if_addr.ip == *ip);
+        if interfaces.is_empty() {
+            let named = matches.contains_id("interface") || matches.contains_id("interface_regex");
+            let selected = if named {
+                "the selected interfaces"
+            } else {
+                "any interface"
+            };
+            println!(
+                "Error: {} is not an address of {}, the addresses are {}",
+                ip,
+                selected,
+                addresses.join(", ")
+            );
+            ExitCode::NoNetworks.exit();
+        }
+    }
+    let all_interfaces = matches.get_flag("all_interfaces");
+    let mut ip_nets: Vec<Ipv4Net> = interfaces
+        .iter()
+        .filter(|if_addr| all_interfaces || if_addr.is_broadcast_candidate())
+        .map(InterfaceAddr::network)
+        .collect();
+    // the same order on every run, whatever order the interfaces come in
+    ip_nets.sort();
+    ip_nets
+}
+
+// keep the addresses of the interfaces named by --interface or matched by
+// --interface-regex, or every address when neither is given
+fn select_interfaces<'a>(
+    interfaces: Vec<InterfaceAddr>,
+    names: Option<impl Iterator<Item = &'a String>>,
+    patterns: Option<impl Iterator<Item = &'a Regex>>,
+) -> Vec<InterfaceAddr> {
+    if names.is_none() && patterns.is_none() {
+        return interfaces;
+    }
+    let names: Vec<&String> = names.map(|v| v.collect()).unwrap_or_default();
+    let patterns: Vec<&Regex> = patterns.map(|v| v.collect()).unwrap_or_default();
+
+    // a typo in a name is reported as such, not as a missing network
+    let mut available: Vec<&str> = interfaces.iter().map(|if_addr| if_addr.name.as_str()).collect();
+    available.sort_unstable();
+    available.dedup();
+    for name in &names {
+        if !available.contains(&name.as_str()) {
+            println!(
+                "Error: there is no interface {}, the interfaces are {}",
+                name,
+                available.join(", ")
+            );
+            ExitCode::NoNetworks.exit();
+        }
+    }
+    for pattern in &patterns {
+        if !interfaces
+            .iter()
+            .any(|if_addr| pattern.is_match(&if_addr.name))
+        {
+            println!(
+                "Error: --interface-regex {} matches no interface, the interfaces are {}",
+                pattern.as_str(),
+                available.join(", ")
+            );
+            ExitCode::NoNetworks.exit();
+        }
+    }
+
+    interfaces
+        .into_iter()
+        .filter(|if_addr| {
+            names.contains(&&if_addr.name)
+                || patterns.iter().any(|pattern| pattern.is_match(&if_addr.name))
+        })
+        .collect()
+}
+
+fn resolve_host(host: &str) -> io::Result<Ipv4Addr> {
+    if let Ok(ip) = host.parse::<Ipv4Addr>() {
+        return Ok(ip);
+    }
+    (host, 0)
+        .to_socket_addrs()?
+        .find_map(|addr| match addr.ip() {
+            IpAddr::V4(ip) => Some(ip),
+            IpAddr::V6(_) => None,
+        })
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no IPv4 address found"))
+}
+
+// wait for the --wake-and-ssh host to accept connections on its SSH port,
+// then run --then or ssh and exit with its exit code
+fn wake_and_ssh(matches: &clap::ArgMatches, destination: &str, run_id: &str) -> ! {
+    // USER@HOST:PORT, ssh takes the port as an option
+    let (login, port) = match destination.rsplit_once(':') {
+        Some((login, port)) if port.parse::<u16>().is_ok() => (login, Some(port)),
+        _ => (destination, None),
+    };
+    let host = login.rsplit_once('@').map_or(login, |(_, host)| host);
+    let probe = match resolve_probe(&format!("{}:{}", host, port.unwrap_or("22")), &[]) {
+        Ok(v) => v,
+        Err(err) => {
+            println!("Error: failed to resolve --wake-and-ssh {}: {}", host, err);
+            ExitCode::InvalidArguments.exit();
+        }
+    };
+
+    let timeout = *matches.get_one::<Duration>("wait_timeout").unwrap();
+    let shown = format_probe(&probe);
+    println!("Waiting up to {:?} for {} to come up", timeout, shown);
+    signal::install();
+    let start = Instant::now();
+    while !host_is_up(&probe) {
+        if start.elapsed() >= timeout {
+            println!("Error: {} didn't come up within {:?}", shown, timeout);
+            ExitCode::TotalFailure.exit();
+        }
+        if !signal::sleep(Duration::from_secs(1)) {
+            println!("Interrupted, {} didn't come up", shown);
+            ExitCode::Interrupted.exit();
+        }
+    }
+
+    let mut command = match matches.get_one::<String>("then") {
+        Some(then) => hooks::shell(then),
+        None => {
+            let mut ssh = std::process::Command::new("ssh");
+            if let Some(port) = port {
+                ssh.args(["-p", port]);
+            }
+            ssh.arg(login);
+            ssh
+        }
+    };
+    println!("{} is up after {:.0?}", shown, start.elapsed());
+    match command.env("WOL_RUN_ID", run_id).status() {
+        Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+        Err(err) => {
+            println!("Error: failed to run the command for {}: {}", destination, err);
+            ExitCode::Io.exit();
+        }
+    }
+}
+
+enum Answer {
+    Yes,
+    No,
+    Quit,
+}
+
+// the --confirm-each prompt for `target`, anything but y or q is a no and
+// the end of the input is a q
+fn ask_to_wake(plan: &WakePlan, target: &Target, mac_case: MacCase) -> Answer {
+    let mut detail = format_target_mac(target, mac_case);
+    if let Some(name) = &target.name {
+        detail += &format!(" ({})", name);
+    }
+    let destinations: Vec<String> = plan
+        .destinations(target)
+        .iter()
+        .map(|network| plan.broadcast(network).to_string())
+        .collect();
+    print!(
+        "{} to {}. Wake this one? [y/N/q] ",
+        detail,
+        destinations.join(", ")
+    );
+    let _ = io::stdout().flush();
+
+    let mut answer = String::new();
+    match io::stdin().lock().read_line(&mut answer) {
+        Ok(0) | Err(_) => {
+            println!();
+            Answer::Quit
+        }
+        Ok(_) => match answer.trim().to_lowercase().as_str() {
+            "y" | "yes" => Answer::Yes,
+            "q" | "quit" => Answer::Quit,
+            _ => Answer::No,
+        },
+    }
+}
 
+// the addresses a --retry-on-down value probes, HOST:PORT, or HOST on every
+// one of `ports`
+fn resolve_probe(value: &str, ports: &[u16]) -> io::Result<Vec<SocketAddr>> {
+    let (host, ports) = match value.rsplit_once(':') {
+        Some((host, port)) => match port.parse::<u16>() {
+            Ok(port) => (host, vec![port]),
+            Err(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid port {}", port),
+                ))
+            }
+        },
+        None => (value, ports.to_vec()),
+    };
+    let addrs: Vec<SocketAddr> = (host, 0).to_socket_addrs()?.collect();
+    let ip = addrs
+        .iter()
+        .find(|addr| addr.is_ipv4())
+        .or_else(|| addrs.first())
+        .map(|addr| addr.ip())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address found"))?;
+    Ok(ports.iter().map(|port| SocketAddr::new(ip, *port)).collect())
+}
+
+// 192.168.1.20:22, or 192.168.1.20 port 22, 80 when several ports are probed
+fn format_probe(addrs: &[SocketAddr]) -> String {
+    match addrs {
+        [addr] => addr.to_string(),
+        _ => {
+            let ports: Vec<String> = addrs.iter().map(|addr| addr.port().to_string()).collect();
+            let ip = addrs.first().map(|addr| addr.ip().to_string()).unwrap_or_default();
+            format!("{} port {}", ip, ports.join(", "))
+        }
+    }
+}
+
+// a host is up when it accepts the TCP connection on any of `addrs`, or
+// refuses it, which only a running host can do. The ports are tried at the
+// same time, so a probe takes at most PROBE_TIMEOUT
+fn host_is_up(addrs: &[SocketAddr]) -> bool {
+    thread::scope(|scope| {
+        let probes: Vec<_> = addrs
+            .iter()
+            .map(|addr| {
+                scope.spawn(move || match TcpStream::connect_timeout(addr, PROBE_TIMEOUT) {
+                    Ok(_) => true,
+                    Err(err) => err.kind() == io::ErrorKind::ConnectionRefused,
+                })
+            })
+            .collect();
+        probes.into_iter().any(|probe| probe.join().unwrap_or(false))
+    })
+}
+
+// a sleeping device doesn't answer ARP, so unicast sends to it fail unless the
+// IP to MAC mapping is already cached. Add it for every unicast target on a
+// local network, this needs privileges so failures only show in verbose mode
+fn add_static_arp_entries(
+    static_arp: &mut arp::StaticEntries,
+    targets: &[Target],
+    host_ip: Option<Ipv4Addr>,
+    verbose_mode: bool,
+) {
+    let pairs: Vec<(Ipv4Addr, MacAddr)> = targets
+        .iter()
+        .filter_map(|target| target.ip.or(host_ip).map(|ip| (ip, target.mac)))
+        .filter(|(ip, _)| !ip.is_loopback())
+        .collect();
+    if pairs.is_empty() {
+        return;
+    }
+    let interfaces = SystemInterfaces.interfaces().unwrap_or_default();
+
+    for (ip, mac_address) in pairs {
+        let device = interfaces
+            .iter()
+            .find(|if_addr| if_addr.network().contains(&ip));
+        let device = match device {
+            Some(v) => &v.name,
+            None => {
+                if verbose_mode {
+                    println!("{} is not on a local network, no ARP entry is needed", ip);
+                }
+                continue;
+            }
+        };
+        match static_arp.ensure(ip, mac_address, device) {
+            Ok(true) => {
+                if verbose_mode {
+                    println!("Added ARP entry {} -> {} on {}", ip, mac_address, device);
+                }
+            }
+            Ok(false) => {}
+            Err(err) => {
+                if verbose_mode {
+                    println!("Warning: failed to add ARP entry for {}: {}", ip, err);
+                }
+            }
+        }
+    }
+}
+
+// the sockets packets are sent from. On Linux every broadcast network
+// on a local interface gets a socket bound to that interface, otherwise the
+// routing table can send its broadcast out of another one
+struct Sockets {
+    default: UdpSocket,
+    by_network: HashMap<Ipv4Net, UdpSocket>,
+}
+
+impl Sockets {
+    fn bind(
+        default: UdpSocket,
+        networks: &[Ipv4Net],
+        no_broadcast: bool,
+        verbose: bool,
+    ) -> Sockets {
+        let mut by_network = HashMap::new();
+        if cfg!(target_os = "linux") && !no_broadcast && !networks.is_empty() {
+            let interfaces = SystemInterfaces.interfaces().unwrap_or_default();
+            for network in networks {
+                let device = interfaces
+                    .iter()
+                    .find(|if_addr| if_addr.network().trunc() == network.trunc());
+                let Some(device) = device else {
+                    continue;
+                };
+                match bind_socket_to_device(&device.name) {
+                    Ok(socket) => {
+                        if verbose {
+                            println!("Broadcasting on {} through {}", network, device.name);
+                        }
+                        by_network.insert(*network, socket);
+                    }
+                    Err(err) => {
+                        if verbose {
+                            println!(
+                                "Can't bind a socket to {}, the routing table picks the \
+                                 interface for {}: {}",
+                                device.name, network, err
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        Sockets {
+            default,
+            by_network,
+        }
+    }
+
+    fn get(&self, network: &Ipv4Net) -> &UdpSocket {
+        self.by_network.get(network).unwrap_or(&self.default)
+    }
+}
+
+// the networks whose interface reports no carrier in
+// /sys/class/net/IF/carrier, e.g. with its cable unplugged, and the
+// interface's name. Only Linux tells, elsewhere nothing is found
+fn no_carrier_networks(networks: &[Ipv4Net]) -> HashMap<Ipv4Net, String> {
+    let mut no_carrier = HashMap::new();
+    if !cfg!(target_os = "linux") || networks.is_empty() {
+        return no_carrier;
+    }
+    let interfaces = SystemInterfaces.interfaces().unwrap_or_default();
+    for network in networks {
+        let device = interfaces
+            .iter()
+            .find(|if_addr| if_addr.network().trunc() == network.trunc());
+        let Some(device) = device else {
+            continue;
+        };
+        let carrier = std::fs::read_to_string(format!("/sys/class/net/{}/carrier", device.name));
+        if carrier.is_ok_and(|carrier| carrier.trim() == "0") {
+            no_carrier.insert(*network, device.name.clone());
+        }
+    }
+    no_carrier
+}
+
+// coarse flow control over the whole run, pausing after every `size` packets
+// so a cheap switch isn't flooded when waking a large fleet
+struct Batcher {
+    size: Option<usize>,
+    pause: Duration,
+    sent: usize,
+    verbose: bool,
+    // --simulate-loss, the percentage of sends to drop
+    loss: Option<(u8, Rng)>,
+    // --burst, the packets sent back to back for every send
+    burst: u32,
+    // --write-pcap, where every sent packet is recorded
+    pcap: Option<pcap::PcapWriter>,
+    // with --strict, the networks whose interface has no carrier, and its
+    // name. Sending there succeeds but the packet goes nowhere
+    no_carrier: HashMap<Ipv4Net, String>,
+}
+
+impl Batcher {
+    // one for the whole run, so the wakes and the keepalives share the
+    // --write-pcap file
+    fn new(matches: &clap::ArgMatches, networks: &[Ipv4Net]) -> Batcher {
+        Batcher {
+            size: matches.get_one::<u64>("batch_size").map(|size| *size as usize),
+            pause: *matches.get_one::<Duration>("batch_pause").unwrap(),
+            sent: 0,
+            verbose: matches.get_flag("verbose"),
+            loss: matches
+                .get_one::<u8>("simulate_loss")
+                .map(|percent| (*percent, Rng::from_time())),
+            burst: *matches.get_one::<u32>("burst").unwrap(),
+            pcap: matches.get_one::<PathBuf>("write_pcap").map(|path| {
+                pcap::PcapWriter::create(path).unwrap_or_else(|err| {
+                    println!("Error: failed to create pcap file {:?}: {}", path, err);
+                    ExitCode::Io.exit();
+                })
+            }),
+            no_carrier: if matches.get_flag("fail_fast") {
+                no_carrier_networks(networks)
+            } else {
+                HashMap::new()
+            },
+        }
+    }
+
+    fn send(
+        &mut self,
+        socket: &UdpSocket,
+        packet: &[u8],
+        network: &Ipv4Net,
+        destination: Ipv4Addr,
+        port: u16,
+    ) -> NetworkResult {
+        if let Some(size) = self.size {
+            if self.sent > 0 && self.sent % size == 0 {
+                if self.verbose {
+                    println!(
+                        "Batch of {} packets sent, pausing for {:?}",
+                        size, self.pause
+                    );
+                }
+                thread::sleep(self.pause);
+            }
+        }
+        self.sent += 1;
+
+        // a --burst is sent back to back and counts as sent when any of its
+        // packets got through
+        let burst_start = Instant::now();
+        let mut net_result = self.send_one(socket, packet, network, destination, port);
+        for _ in 1..self.burst {
+            if net_result.result.is_ok() {
+                self.send_one(socket, packet, network, destination, port);
+            } else {
+                net_result = self.send_one(socket, packet, network, destination, port);
+            }
+        }
+        net_result.elapsed = burst_start.elapsed();
+        if let (Some(device), Ok(_)) = (self.no_carrier.get(network), &net_result.result) {
+            net_result.result = Err(WakeError::Io(io::Error::other(format!(
+                "{} has no carrier, the packet didn't leave the interface",
+                device
+            ))));
+        }
+        net_result
+    }
+
+    fn send_one(
+        &mut self,
+        socket: &UdpSocket,
+        packet: &[u8],
+        network: &Ipv4Net,
+        destination: Ipv4Addr,
+        port: u16,
+    ) -> NetworkResult {
+        if let Some((percent, rng)) = &mut self.loss {
+            if rng.below(100) < u64::from(*percent) {
+                println!(
+                    "Dropped packet to {} port {} (--simulate-loss)",
+                    destination, port
+                );
+                return NetworkResult {
+                    network: *network,
+                    port,
+                    result: Err(WakeError::Io(io::Error::other("dropped by --simulate-loss"))),
+                    elapsed: Duration::ZERO,
+                };
+            }
+        }
+        let net_result = send_packet(socket, packet, network, destination, port);
+        if let (Some(pcap), Ok(_)) = (&mut self.pcap, &net_result.result) {
+            let source_port = socket.local_addr().map(|addr| addr.port()).unwrap_or(0);
+            let destination = SocketAddrV4::new(destination, port);
+            let broadcast = network.prefix_len() < 32;
+            if let Err(err) = pcap.write_udp(source_port, destination, broadcast, packet) {
+                println!(
+                    "Warning: failed to write the pcap file, no more packets are recorded: {}",
+                    err
+                );
+                self.pcap = None;
+            }
+        }
+        net_result
+    }
+}
+
+// min/max/avg over every send, including retries
+fn format_timing_stats(send_times: &[Duration]) -> String {
+    if send_times.is_empty() {
+        return "0 sends".to_string();
+    }
+    let min = send_times.iter().min().unwrap();
+    let max = send_times.iter().max().unwrap();
+    let avg = send_times.iter().sum::<Duration>() / send_times.len() as u32;
+    format!(
+        "{} sends, min {:.3?}, max {:.3?}, avg {:.3?}",
+        send_times.len(),
+        min,
+        max,
+        avg
+    )
+}
+
+#[cfg(feature = "mdns")]
+fn discover_devices(timeout: Duration, mac_case: MacCase) -> ExitCode {
+    let devices = match mdns::discover(timeout) {
+        Ok(v) => v,
+        Err(err) => {
+            println!("Error: mDNS discovery failed: {}", err);
+            return ExitCode::Io;
+        }
+    };
+    for device in devices {
+        let mac_display = match device.mac {
+            Some(mac_address) => format_mac(&mac_address, mac_case),
+            None => "unknown".to_string(),
+        };
+        println!("{:<17}  {:<15}  {}", mac_display, device.ip, device.hostname);
+    }
+    ExitCode::Success
+}
+
+#[cfg(not(feature = "mdns"))]
+fn discover_devices(_timeout: Duration, _mac_case: MacCase) -> ExitCode {
+    println!("Error: mDNS discovery is not available in this build, rebuild with --features mdns");
+    ExitCode::InvalidArguments
+}
+
+// "1 device", "3 devices"
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("{} {}", count, noun)
+    } else {
+        format!("{} {}s", count, noun)
+    }
+}
+
+// "9", "7 and 9", "7, 9 and 4000"
+fn join_list<T: ToString>(items: &[T]) -> String {
+    let items: Vec<String> = items.iter().map(ToString::to_string).collect();
+    match items.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{} and {}", rest.join(", "), last),
+        _ => items.join(""),
+    }
+}
+
+// the plan of the run in plain English, for --explain
+fn explain(matches: &clap::ArgMatches, plan: &WakePlan) -> String {
+    let targets = &plan.targets;
+    let host_ip = plan.host_ip;
+    let networks = &plan.networks;
+    let ports = &plan.ports;
+    let unicast = targets
+        .iter()
+        .filter(|target| target.ip.or(host_ip).is_some())
+        .count();
+    let broadcast = targets.len() - unicast;
+
+    let mut destinations = Vec::new();
+    if broadcast > 0 {
+        destinations.push(format!(
+            "to the broadcast address of {} ({})",
+            plural(networks.len(), "network"),
+            join_list(networks)
+        ));
+    }
+    if let Some(ip) = host_ip {
+        destinations.push(format!("by unicast to {}", ip));
+    } else if unicast > 0 {
+        destinations.push(format!(
+            "by unicast to the IP address of {}",
+            plural(unicast, "device")
+        ));
+    }
+    let port_label = if ports.len() == 1 { "port" } else { "ports" };
+    let mut plan = format!(
+        "Will send a Wake-on-LAN packet for {} {} on UDP {} {}",
+        plural(targets.len(), "device"),
+        destinations.join(" and "),
+        port_label,
+        join_list(ports)
+    );
+
+    let burst = *matches.get_one::<u32>("burst").unwrap();
+    if burst > 1 {
+        plan += &format!(" in bursts of {} back to back", burst);
+    }
+    let repeat_count = *matches.get_one::<u32>("repeat").unwrap();
+    if repeat_count > 1 {
+        let delay = matches.get_one::<Duration>("delay").unwrap();
+        let pause = if !matches.get_flag("backoff") {
+            format!("{:?} apart", delay)
+        } else if let Some(max_delay) = matches.get_one::<Duration>("max_delay") {
+            format!("with a pause doubling from {:?} up to {:?}", delay, max_delay)
+        } else {
+            format!("with a pause doubling from {:?}", delay)
+        };
+        plan += &format!(", repeating {} times {}", repeat_count, pause);
+    }
+    if let Some(size) = matches.get_one::<u64>("batch_size") {
+        plan += &format!(
+            ", in batches of {} with a {:?} pause",
+            plural(*size as usize, "packet"),
+            matches.get_one::<Duration>("batch_pause").unwrap()
+        );
+    }
+    let retry_networks = *matches.get_one::<u32>("retry_networks").unwrap();
+    if retry_networks > 0 {
+        plan += &format!(
+            ", retrying failed networks up to {}",
+            plural(retry_networks as usize, "time")
+        );
+    }
+    if let Some(host) = matches.get_one::<String>("retry_on_down") {
+        plan += &format!(", stopping early once {} answers a TCP connection", host);
+    }
+    plan.push('.');
+    plan
+}
+
+// how the MAC address of every target was written, for --mac-case as-is
+type MacTexts = HashMap<MacAddr, String>;
+
+// how MAC addresses are printed, --mac-case or --lowercase
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MacCase<'a> {
+    Upper,
+    Lower,
+    // the way the target was written, e.g. 0011.2233.4455 stays dotted. The
+    // texts come with the targets, see with_texts
+    AsIs(Option<&'a MacTexts>),
+}
+
+impl MacCase<'_> {
+    // the same case, printing the targets as-is the way `texts` holds them
+    fn with_texts(self, texts: &MacTexts) -> MacCase<'_> {
+        match self {
+            MacCase::Upper => MacCase::Upper,
+            MacCase::Lower => MacCase::Lower,
+            MacCase::AsIs(_) => MacCase::AsIs(Some(texts)),
+        }
+    }
+}
+
+fn read_mac_case(matches: &clap::ArgMatches) -> MacCase<'static> {
+    match matches.get_one::<String>("mac_case").map(String::as_str) {
+        Some("lower") => MacCase::Lower,
+        Some("as-is") => MacCase::AsIs(None),
+        Some(_) => MacCase::Upper,
+        None if matches.get_flag("lowercase") => MacCase::Lower,
+        None => MacCase::Upper,
+    }
+}
+
+// a MAC address that isn't a target's, e.g. from the ARP cache, has no
+// written form, so as-is prints it in uppercase
+fn format_mac(mac_address: &MacAddr, mac_case: MacCase) -> String {
+    match mac_case {
+        MacCase::Lower => format!("{:x}", mac_address),
+        MacCase::Upper | MacCase::AsIs(_) => mac_address.to_string(),
+    }
+}
+
+fn format_target_mac(target: &Target, mac_case: MacCase) -> String {
+    let written = match mac_case {
+        MacCase::AsIs(Some(texts)) => texts.get(&target.mac),
+        _ => None,
+    };
+    match written {
+        Some(text) => text.clone(),
+        None => format_mac(&target.mac, mac_case),
+    }
+}
+
+// the MAC addresses of `field` written the way the field is. A single MAC
+// address keeps its text, the addresses of a range get the separators and
+// the case of the range
+fn written_macs(field: &str, mac_addresses: &[MacAddr]) -> Vec<String> {
+    if let [_] = mac_addresses {
+        return vec![field.to_string()];
+    }
+    let bytes = field.as_bytes();
+    let separator = match bytes.get(2) {
+        Some(b':') => ":",
+        Some(b'-') => "-",
+        _ => "",
+    };
+    let dotted = separator.is_empty() && bytes.get(4) == Some(&b'.');
+    let lowercase = field
+        .chars()
+        .find(|c| c.is_ascii_hexdigit() && c.is_ascii_alphabetic())
+        .is_some_and(|c| c.is_ascii_lowercase());
     mac_addresses
+        .iter()
+        .map(|mac| {
+            let digits: Vec<String> = mac
+                .octets()
+                .iter()
+                .map(|byte| match lowercase {
+                    true => format!("{:02x}", byte),
+                    false => format!("{:02X}", byte),
+                })
+                .collect();
+            if dotted {
+                digits.chunks(2).map(|pair| pair.concat()).collect::<Vec<_>>().join(".")
+            } else {
+                digits.join(separator)
+            }
+        })
+        .collect()
+}
+
+// the lease format the --file input is read in, None for the target file
+// format. --file-format auto looks at the content
+fn file_format(
+    matches: &clap::ArgMatches,
+    file_path: &Path,
+    verbose_mode: bool,
+) -> Option<leases::LeaseFormat> {
+    match matches.get_one::<String>("file_format").unwrap().as_str() {
+        "dnsmasq" => Some(leases::LeaseFormat::Dnsmasq),
+        "isc" => Some(leases::LeaseFormat::Isc),
+        "plain" | "hex-lines" => None,
+        _ => {
+            // a file that can't be read is reported by the target file reader
+            let content = std::fs::read(file_path).ok()?;
+            let format = leases::detect(&String::from_utf8_lossy(&content))?;
+            if verbose_mode {
+                let name = match format {
+                    leases::LeaseFormat::Dnsmasq => "dnsmasq",
+                    leases::LeaseFormat::Isc => "ISC dhcpd",
+                };
+                println!("Reading {:?} as a {} lease file", file_path, name);
+            }
+            Some(format)
+        }
+    }
+}
+
+// the targets of a lease file and how many leases were skipped. With
+// `fail_fast` the first skipped lease ends the run
+fn read_leases(
+    lease_path: &Path,
+    format: leases::LeaseFormat,
+    fail_fast: bool,
+    mac_texts: &mut MacTexts,
+) -> (Vec<Target>, usize) {
+    let content = match std::fs::read_to_string(lease_path) {
+        Ok(v) => v,
+        Err(err) => {
+            println!("Error: failed to read {:?}: {}", lease_path, err);
+            ExitCode::Io.exit();
+        }
+    };
+    let (targets, skipped) = leases::parse_leases(&content, format);
+    if let (true, Some(err)) = (fail_fast, skipped.first()) {
+        println!("Error: {:?}: {}", lease_path, err);
+        ExitCode::InvalidArguments.exit();
+    }
+    for err in &skipped {
+        println!("Warning: skipping {}", err);
+    }
+    let targets = targets
+        .into_iter()
+        .map(|(target, text)| {
+            mac_texts.entry(target.mac).or_insert(text);
+            target
+        })
+        .collect();
+    (targets, skipped.len())
+}
+
+// the targets of a --file-format hex-lines file, the packet for each and how
+// many lines were skipped. With `fail_fast` the first invalid line ends the
+// run
+fn read_packet_lines(
+    file_path: &Path,
+    fail_fast: bool,
+) -> (Vec<Target>, HashMap<MacAddr, Vec<u8>>, usize) {
+    let content = match std::fs::read_to_string(file_path) {
+        Ok(v) => v,
+        Err(err) => {
+            println!("Error: failed to read {:?}: {}", file_path, err);
+            ExitCode::Io.exit();
+        }
+    };
+    let mut targets = Vec::new();
+    let mut packets: HashMap<MacAddr, Vec<u8>> = HashMap::new();
+    let mut skipped = 0;
+    for (line_index, line) in content.lines().enumerate() {
+        let result = parse_packet_line(line).and_then(|parsed| match parsed {
+            Some((mac, packet)) => match packets.get(&mac) {
+                // the same packet twice is only sent once
+                Some(known) if *known == packet => Ok(None),
+                Some(_) => Err(format!("an earlier line has another packet for {}", mac)),
+                None => Ok(Some((mac, packet))),
+            },
+            None => Ok(None),
+        });
+        match result {
+            Ok(Some((mac, packet))) => {
+                targets.push(Target::new(mac));
+                packets.insert(mac, packet);
+            }
+            Ok(None) => {}
+            Err(err) if fail_fast => {
+                println!("Error: {:?} line {}: {}", file_path, line_index + 1, err);
+                ExitCode::InvalidArguments.exit();
+            }
+            Err(err) => {
+                println!("Warning: skipping {:?} line {}: {}", file_path, line_index + 1, err);
+                skipped += 1;
+            }
+        }
+    }
+    (targets, packets, skipped)
+}
+
+// the targets in the file, the interval of those with a keepalive attribute
+// and how many invalid lines were skipped. With `fail_fast` the first invalid
+// line ends the run
+fn read_targets_from_file(
+    file_path: &Path,
+    fail_fast: bool,
+    allow_exec: bool,
+    mac_texts: &mut MacTexts,
+) -> (Vec<Target>, Vec<(Target, Duration)>, usize) {
+    let mut targets = Vec::new();
+    let mut keepalives = Vec::new();
+    let mut skipped = 0;
+
+    let mut on_line = |path: &Path, line_number: usize, line: &str| {
+        // skip lines that are not a valid target
+        let TargetLine {
+            targets: line_targets,
+            keepalive,
+            order,
+        } = match parse_target_line(line) {
+            Ok(Some(v)) => v,
+            Ok(None) => return,
+            Err(err) if fail_fast => {
+                println!("Error: {:?} line {}: {}", path, line_number, err);
+                ExitCode::InvalidArguments.exit();
+            }
+            Err(err) => {
+                println!("Warning: skipping {:?} line {}: {}", path, line_number, err);
+                skipped += 1;
+                return;
+            }
+        };
+        for (target, text) in line_targets {
+            if let Some(interval) = keepalive {
+                keepalives.push((target.clone(), interval));
+            }
+            mac_texts.entry(target.mac).or_insert(text);
+            targets.push((order, target));
+        }
+    };
+    let walked = walk_target_file(file_path, &mut Vec::new(), allow_exec, &mut on_line);
+    if let Err(err) = walked {
+        println!("Error: {}", err);
+        ExitCode::Io.exit();
+    }
+    // order= first, lowest first, then the targets without one, each in file
+    // order
+    targets.sort_by_key(|(order, _)| (order.is_none(), *order));
+    let targets = targets.into_iter().map(|(_, target)| target).collect();
+
+    (targets, keepalives, skipped)
+}
+
+// call `on_line` with the path, number and text of every line of a targets
+// file, and of the files it pulls in with "#include PATH" in their place.
+// PATH is relative to the including file, `stack` holds the files being read
+// to catch include cycles
+fn walk_target_file(
+    file_path: &Path,
+    stack: &mut Vec<PathBuf>,
+    allow_exec: bool,
+    on_line: &mut impl FnMut(&Path, usize, &str),
+) -> Result<(), String> {
+    let read_error = |err: io::Error| format!("failed to read {:?}: {}", file_path, err);
+    let canonical = file_path.canonicalize().map_err(read_error)?;
+    if stack.contains(&canonical) {
+        return Err(format!("{:?} includes itself", file_path));
+    }
+    // invalid UTF-8 only spoils the lines it is on
+    let content = std::fs::read(file_path).map_err(read_error)?;
+    let content = String::from_utf8_lossy(&content);
+
+    stack.push(canonical);
+    for (line_index, line) in content.lines().enumerate() {
+        let include = line
+            .trim()
+            .strip_prefix("#include")
+            .filter(|rest| rest.starts_with(char::is_whitespace));
+        match include {
+            Some(include) => {
+                let include = include.trim().trim_matches('"');
+                let included = file_path.parent().unwrap_or(Path::new("")).join(include);
+                walk_target_file(&included, stack, allow_exec, on_line).map_err(|err| {
+                    format!("{:?} line {}: {}", file_path, line_index + 1, err)
+                })?;
+            }
+            // with --allow-exec, a !COMMAND line stands for the lines the
+            // command prints
+            None if allow_exec && line.trim().starts_with('!') => {
+                let command = &line.trim()[1..];
+                let output = run_target_command(command).map_err(|err| {
+                    format!("{:?} line {}: {}", file_path, line_index + 1, err)
+                })?;
+                for output_line in output.lines() {
+                    on_line(file_path, line_index + 1, output_line);
+                }
+            }
+            None => on_line(file_path, line_index + 1, line),
+        }
+    }
+    stack.pop();
+
+    Ok(())
+}
+
+// the standard output of a !COMMAND target file line
+fn run_target_command(command: &str) -> Result<String, String> {
+    let output = hooks::shell(command)
+        .stdin(std::process::Stdio::null())
+        .stderr(std::process::Stdio::inherit())
+        .output()
+        .map_err(|err| format!("failed to run {:?}: {}", command, err))?;
+    if !output.status.success() {
+        return Err(format!("{:?} failed with {}", command, output.status));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// the most addresses a MAC address range expands to, so a typo in a range
+// can't wake a whole vendor
+const MAX_RANGE_LEN: u64 = 4096;
+
+// a MAC address, or a range of sequential ones written FIRST-LAST or
+// FIRST+COUNT, e.g. 00:11:22:33:44:00-00:11:22:33:44:0f or
+// 00:11:22:33:44:00+16
+fn parse_mac_range(value: &str) -> Result<Vec<MacAddr>, String> {
+    let to_number = |mac: MacAddr| {
+        let bytes = mac.octets();
+        u64::from_be_bytes([0, 0, bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5]])
+    };
+    let (first, count) = if let Some((first, count)) = value.split_once('+') {
+        let first = first.parse::<MacAddr>().map_err(|err| WakeError::from(err).to_string())?;
+        let count = match count.parse::<u64>() {
+            Ok(v) if v > 0 => v,
+            _ => return Err(format!("invalid count {} in MAC address range", count)),
+        };
+        (to_number(first), count)
+    } else {
+        let err = match value.parse::<MacAddr>() {
+            Ok(v) => return Ok(vec![v]),
+            Err(err) => WakeError::from(err).to_string(),
+        };
+        // the dashes of 00-11-22-33-44-55 can't be told from the one between
+        // FIRST and LAST, so try every split
+        let range = value.match_indices('-').find_map(|(i, _)| {
+            let first = value[..i].parse::<MacAddr>().ok()?;
+            let last = value[i + 1..].parse::<MacAddr>().ok()?;
+            Some((first, last))
+        });
+        let Some((first, last)) = range else {
+            return Err(err);
+        };
+        let (first, last) = (to_number(first), to_number(last));
+        if last < first {
+            return Err("the MAC address range ends before it starts".to_string());
+        }
+        (first, last - first + 1)
+    };
+    if count > MAX_RANGE_LEN {
+        return Err(format!(
+            "the MAC address range has {} addresses, more than {}",
+            count, MAX_RANGE_LEN
+        ));
+    }
+    if first + count - 1 > 0xFFFF_FFFF_FFFF {
+        return Err("the MAC address range goes past FF:FF:FF:FF:FF:FF".to_string());
+    }
+    Ok((first..first + count)
+        .map(|n| {
+            let bytes = n.to_be_bytes();
+            MacAddr::new([bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]])
+        })
+        .collect())
+}
+
+// the targets read from a line of a targets file, more than one for a MAC
+// address range, with their attributes
+struct TargetLine {
+    // every target with its MAC address as it was written
+    targets: Vec<(Target, String)>,
+    // keepalive=, how often the target is woken again
+    keepalive: Option<Duration>,
+    // order=, targets with a lower order are woken first
+    order: Option<u32>,
+}
+
+// empty lines and comments, which start with #, // or ; like in INI style
+// files, have no target
+fn is_skipped_line(line: &str) -> bool {
+    let line = line.trim();
+    line.is_empty() || line.starts_with('#') || line.starts_with("//") || line.starts_with(';')
+}
+
+// a line of a targets file: a MAC address, optionally followed by the
+// device's IP address and key=value attributes. Blank and comment lines give
+// None
+fn parse_target_line(line: &str) -> Result<Option<TargetLine>, String> {
+    let line = line.trim();
+    if is_skipped_line(line) {
+        return Ok(None);
+    }
+    // commands are only run with --allow-exec, walk_target_file has already
+    // expanded them when it is given
+    if line.starts_with('!') {
+        return Err("command lines are only run with --allow-exec".to_string());
+    }
+
+    let mut fields = line.split_whitespace().peekable();
+    let mac_field = fields.next().unwrap();
+    let mac_addresses = parse_mac_range(mac_field)
+        .map_err(|err| format!("{}, original MAC address: {}", err, mac_field))?;
+    let ip = match fields.next_if(|field| !field.contains('=')) {
+        Some(field) => Some(
+            field
+                .parse::<Ipv4Addr>()
+                .map_err(|_| format!("invalid IP address {}", field))?,
+        ),
+        None => None,
+    };
+    let mut keepalive = None;
+    let mut order = None;
+    for field in fields {
+        match field.split_once('=') {
+            Some(("keepalive", value)) => match duration::parse_duration(value) {
+                Ok(v) if !v.is_zero() => keepalive = Some(v),
+                Ok(_) => return Err("keepalive interval must be greater than zero".to_string()),
+                Err(err) => return Err(format!("invalid keepalive interval {}: {}", value, err)),
+            },
+            Some(("order", value)) => match value.parse::<u32>() {
+                Ok(v) => order = Some(v),
+                Err(_) => return Err(format!("invalid order {}, should be a number", value)),
+            },
+            _ => return Err(format!("unknown attribute {}", field)),
+        }
+    }
+
+    if ip.is_some() && mac_addresses.len() > 1 {
+        return Err(format!("the MAC address range {} can't have one IP address", mac_field));
+    }
+
+    let texts = written_macs(mac_field, &mac_addresses);
+    Ok(Some(TargetLine {
+        targets: mac_addresses
+            .into_iter()
+            .map(|mac| Target {
+                mac,
+                ip,
+                name: None,
+            })
+            .zip(texts)
+            .collect(),
+        keepalive,
+        order,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::error::ErrorKind;
+
+    #[test]
+    fn no_arguments_is_a_usage_error() {
+        let err = cli().try_get_matches_from(["wol"]).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn empty_net_is_a_usage_error() {
+        let err = cli()
+            .try_get_matches_from(["wol", "00:11:22:33:44:55", "--net"])
+            .unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidValue);
+    }
+
+    #[test]
+    fn net_takes_a_single_value() {
+        let matches = cli()
+            .try_get_matches_from(["wol", "-n", "192.168.1.0/24", "00:11:22:33:44:55"])
+            .unwrap();
+
+        let nets: Vec<&String> = matches.get_many("net").unwrap().collect();
+        let macs: Vec<&String> = matches.get_many("mac_address").unwrap().collect();
+        assert_eq!(nets, ["192.168.1.0/24"]);
+        assert_eq!(macs, ["00:11:22:33:44:55"]);
+    }
+
+    #[test]
+    fn file_makes_positional_addresses_optional() {
+        let matches = cli().try_get_matches_from(["wol", "-f", "macs.txt"]).unwrap();
+
+        assert!(matches.get_many::<String>("mac_address").is_none());
+    }
+
+    #[test]
+    fn file_only_requires_a_file() {
+        let err = cli()
+            .try_get_matches_from(["wol", "--file-only", "00:11:22:33:44:55"])
+            .unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+    }
+
+    // the targets read from a file holding `content`
+    fn read_targets(name: &str, content: &[u8]) -> Vec<Target> {
+        read_targets_and_skipped(name, content).0
+    }
+
+    // the targets and the number of invalid lines skipped with a warning
+    fn read_targets_and_skipped(name: &str, content: &[u8]) -> (Vec<Target>, usize) {
+        let path = std::env::temp_dir().join(format!("wol-test-{}-{}", std::process::id(), name));
+        std::fs::write(&path, content).unwrap();
+        let (targets, _, skipped) =
+            read_targets_from_file(&path, false, false, &mut MacTexts::new());
+        std::fs::remove_file(&path).unwrap();
+        (targets, skipped)
+    }
+
+    #[test]
+    fn net_file_networks_follow_net_with_their_line() {
+        let path = std::env::temp_dir().join(format!("wol-test-{}-nets", std::process::id()));
+        std::fs::write(&path, "# lan\n192.168.1.0/24\n\n; lab\n10.0.0.0/8\n").unwrap();
+        let matches = cli()
+            .try_get_matches_from([
+                "wol",
+                "-n",
+                "auto",
+                "--net-file",
+                path.to_str().unwrap(),
+                "00:11:22:33:44:55",
+            ])
+            .unwrap();
+        let values = net_values(&matches);
+        std::fs::remove_file(&path).unwrap();
+
+        let location = |line| Some(format!("{:?} line {}", path, line));
+        assert_eq!(
+            values,
+            [
+                ("auto".to_string(), None),
+                ("192.168.1.0/24".to_string(), location(2)),
+                ("10.0.0.0/8".to_string(), location(5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn targets_are_sorted_by_order_then_file_order() {
+        let targets = read_targets(
+            "order",
+            b"00:00:00:00:00:01\n00:00:00:00:00:02 order=20\n\
+              00:00:00:00:00:03 10.0.0.3 order=10\n00:00:00:00:00:04 order=10\n",
+        );
+        let last_bytes: Vec<u8> = targets.iter().map(|target| target.mac.octets()[5]).collect();
+        assert_eq!(last_bytes, [3, 4, 2, 1]);
+    }
+
+    #[test]
+    fn mac_ranges_expand_in_order() {
+        let last_bytes = |value| -> Vec<u8> {
+            parse_mac_range(value)
+                .unwrap()
+                .iter()
+                .map(|mac| mac.octets()[5])
+                .collect()
+        };
+        assert_eq!(last_bytes("00:11:22:33:44:fe-00:11:22:33:45:01"), [0xfe, 0xff, 0, 1]);
+        assert_eq!(last_bytes("00-11-22-33-44-00-00-11-22-33-44-02"), [0, 1, 2]);
+        assert_eq!(last_bytes("00:11:22:33:44:00+3"), [0, 1, 2]);
+        assert_eq!(last_bytes("00:11:22:33:44:55"), [0x55]);
+
+        assert!(parse_mac_range("00:11:22:33:44:02-00:11:22:33:44:00").is_err());
+        assert!(parse_mac_range("00:11:22:33:44:00+0").is_err());
+        assert!(parse_mac_range("00:11:22:33:00:00+4097").is_err());
+        assert!(parse_mac_range("ff:ff:ff:ff:ff:ff+2").is_err());
+    }
+
+    #[test]
+    fn packet_lines_wake_the_mac_address_they_hold() {
+        let mac: MacAddr = "00:11:22:33:44:55".parse().unwrap();
+        let magic_packet = hex(&build_magic_packet(&mac));
+        let (parsed, packet) = parse_packet_line(&magic_packet).unwrap().unwrap();
+        assert_eq!((parsed, packet.len()), (mac, 102));
+
+        // a prefix before the header and a password after the copies
+        let line = format!("57:4f:4c {}deadbeef0001", magic_packet);
+        let (parsed, packet) = parse_packet_line(&line).unwrap().unwrap();
+        assert_eq!((parsed, packet.len()), (mac, 111));
+
+        assert!(parse_packet_line("  # captured on eth0").unwrap().is_none());
+        assert!(parse_packet_line(&magic_packet[..200]).is_err());
+        assert!(parse_packet_line("ffffffffffff0011").is_err());
+        assert!(parse_packet_line("not hex").is_err());
+    }
+
+    #[test]
+    fn mixed_separators_are_rejected_by_name() {
+        for mac_address in ["00:11-22:33-44:55", "00-11-22-33-44:55"] {
+            let err = parse_mac_range(mac_address).unwrap_err();
+            assert!(err.contains("mixes : and -"), "{}", err);
+        }
+        assert!(parse_mac_range("00:11:22:33:44:55").is_ok());
+        assert!(parse_mac_range("00-11-22-33-44-55").is_ok());
+    }
+
+    #[test]
+    fn probe_ports_are_used_when_the_host_has_no_port() {
+        let ip: IpAddr = "127.0.0.1".parse().unwrap();
+        let probe = resolve_probe("127.0.0.1", &[22, 9100]).unwrap();
+        assert_eq!(probe, vec![SocketAddr::new(ip, 22), SocketAddr::new(ip, 9100)]);
+        assert_eq!(format_probe(&probe), "127.0.0.1 port 22, 9100");
+
+        let probe = resolve_probe("127.0.0.1:80", &[22, 9100]).unwrap();
+        assert_eq!(probe, vec![SocketAddr::new(ip, 80)]);
+        assert_eq!(format_probe(&probe), "127.0.0.1:80");
+    }
+
+    #[test]
+    fn a_host_is_up_when_any_probe_port_answers() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.local_addr().unwrap();
+        // TEST-NET-1 neither accepts nor refuses, the probe times out
+        let silent: SocketAddr = "192.0.2.1:22".parse().unwrap();
+        assert!(host_is_up(&[silent, open]));
+    }
+
+    #[test]
+    fn stats_follow_the_targets_left_by_min_interval() {
+        let target = |last_byte, name: &str| Target {
+            name: Some(name.to_string()),
+            ..Target::new(MacAddr::new([0x02, 0, 0, 0, 0, last_byte]))
+        };
+        let targets = [target(1, "one"), target(2, "two"), target(3, "three")];
+        let now = SystemTime::now();
+        let mut history = history::WakeHistory::new(Path::new("unused"));
+        history.record(targets[0].mac, now);
+
+        let left = not_recently_woken(
+            &targets,
+            Some((&history, Duration::from_secs(60))),
+            now,
+            true,
+            MacCase::Upper,
+        );
+        assert_eq!(left, targets[1..]);
+        let outcomes: Vec<(MacAddr, Vec<NetworkResult>)> =
+            left.iter().map(|target| (target.mac, Vec::new())).collect();
+        let json = stats_json(&left, "run", &outcomes, 1, Instant::now());
+        assert!(json.contains(r#"{"mac":"02:00:00:00:00:02","name":"two","#), "{}", json);
+        assert!(json.contains(r#"{"mac":"02:00:00:00:00:03","name":"three","#), "{}", json);
+        assert!(!json.contains(r#""one""#), "{}", json);
+    }
+
+    #[test]
+    fn as_is_prints_targets_the_way_they_were_written() {
+        let path = std::env::temp_dir().join(format!("wol-test-{}-as-is", std::process::id()));
+        std::fs::write(&path, b"0011.2233.44aa\n00-11-22-33-44-bb\n001122334455+2\n").unwrap();
+        let mut texts = MacTexts::new();
+        let (targets, _, _) = read_targets_from_file(&path, false, false, &mut texts);
+        std::fs::remove_file(&path).unwrap();
+        let printed: Vec<String> = targets
+            .iter()
+            .map(|target| format_target_mac(target, MacCase::AsIs(Some(&texts))))
+            .collect();
+        assert_eq!(
+            printed,
+            ["0011.2233.44aa", "00-11-22-33-44-bb", "001122334455", "001122334456"]
+        );
+        assert_eq!(format_target_mac(&targets[0], MacCase::Upper), "00:11:22:33:44:AA");
+        assert_eq!(format_target_mac(&targets[1], MacCase::Lower), "00:11:22:33:44:bb");
+
+        let range = parse_mac_range("00:11:22:33:44:fe+2").unwrap();
+        assert_eq!(
+            written_macs("00:11:22:33:44:fe+2", &range),
+            ["00:11:22:33:44:fe", "00:11:22:33:44:ff"]
+        );
+        let range = parse_mac_range("0011.2233.44FE-0011.2233.44FF").unwrap();
+        assert_eq!(
+            written_macs("0011.2233.44FE-0011.2233.44FF", &range),
+            ["0011.2233.44FE", "0011.2233.44FF"]
+        );
+    }
+
+    #[test]
+    fn empty_and_comment_only_files_have_no_targets() {
+        assert!(read_targets("empty", b"").is_empty());
+        assert!(read_targets("comments", b"# one\n// two\n\n   \n#three\n; four").is_empty());
+    }
+
+    #[test]
+    fn command_lines_without_allow_exec_are_skipped() {
+        let (targets, skipped) =
+            read_targets_and_skipped("exec", b"!echo 00:11:22:33:44:55\n00:11:22:33:44:66\n");
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].mac, MacAddr::new([0x00, 0x11, 0x22, 0x33, 0x44, 0x66]));
+        assert_eq!(skipped, 1);
+    }
+
+    #[test]
+    fn garbage_lines_are_skipped() {
+        let mut content: Vec<u8> = (0..=255).collect();
+        content.extend_from_slice(b"\n\xff\xfe\x00\n00:11:22:33:44:55\n\x00");
+
+        let (targets, skipped) = read_targets_and_skipped("garbage", &content);
+        // every line but the MAC address: the bytes before and after the
+        // first newline, the invalid UTF-8 and the trailing NUL
+        assert_eq!(skipped, 4);
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].mac, MacAddr::new([0x00, 0x11, 0x22, 0x33, 0x44, 0x55]));
+    }
+
+    #[test]
+    fn long_lines_are_skipped() {
+        let mut content = "a".repeat(1 << 20);
+        content.push_str("\n00:11:22:33:44:55 ");
+        content.push_str(&":".repeat(1 << 20));
+
+        let (targets, skipped) = read_targets_and_skipped("long", content.as_bytes());
+        assert!(targets.is_empty());
+        assert_eq!(skipped, 2);
+    }
 }
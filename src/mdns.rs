@@ -0,0 +1,64 @@
+use mdns_sd::{ServiceDaemon, ServiceEvent};
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::Ipv4Addr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::arp;
+use wol::MacAddr;
+
+// service types advertised by desktops (avahi) and macOS devices
+const SERVICE_TYPES: [&str; 2] = ["_workstation._tcp.local.", "_device-info._tcp.local."];
+
+pub struct DiscoveredDevice {
+    pub hostname: String,
+    pub ip: Ipv4Addr,
+    pub mac: Option<MacAddr>,
+}
+
+// browse for devices until the timeout expires, then look up the MAC address
+// of every IPv4 address that was found in the ARP cache
+pub fn discover(timeout: Duration) -> Result<Vec<DiscoveredDevice>, Box<dyn std::error::Error>> {
+    let daemon = ServiceDaemon::new()?;
+    let receivers = SERVICE_TYPES
+        .iter()
+        .map(|service_type| daemon.browse(service_type))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut hosts: BTreeMap<String, BTreeSet<Ipv4Addr>> = BTreeMap::new();
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        for receiver in &receivers {
+            while let Ok(event) = receiver.try_recv() {
+                if let ServiceEvent::ServiceResolved(info) = event {
+                    hosts
+                        .entry(info.get_hostname().trim_end_matches('.').to_string())
+                        .or_default()
+                        .extend(info.get_addresses_v4());
+                }
+            }
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    let _ = daemon.shutdown();
+
+    // the devices only end up in the ARP cache once we have talked to them
+    for ip in hosts.values().flatten() {
+        let _ = arp::probe(*ip);
+    }
+    thread::sleep(Duration::from_millis(200));
+    let arp_table = arp::read_arp_table().unwrap_or_default();
+
+    let mut devices = Vec::new();
+    for (hostname, ips) in hosts {
+        for ip in ips {
+            devices.push(DiscoveredDevice {
+                hostname: hostname.clone(),
+                ip,
+                mac: arp::lookup(&arp_table, ip).map(|entry| entry.mac),
+            });
+        }
+    }
+
+    Ok(devices)
+}
@@ -0,0 +1,276 @@
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// MQTT 3.1.1 control packet types, in the high nibble of the first byte
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+// QoS 1, so the broker confirms every message with a PUBACK
+const PUBLISH_QOS_1: u8 = 0x32;
+const PUBACK: u8 = 0x40;
+const DISCONNECT: u8 = 0xE0;
+
+// a broker given as mqtt://HOST[:PORT] or, with TLS, mqtts://HOST[:PORT]
+pub struct Broker {
+    pub host: String,
+    pub port: u16,
+    pub tls: bool,
+}
+
+pub fn parse_broker(url: &str) -> Result<Broker, String> {
+    let (tls, rest) = if let Some(rest) = url.strip_prefix("mqtts://") {
+        (true, rest)
+    } else if let Some(rest) = url.strip_prefix("mqtt://") {
+        (false, rest)
+    } else {
+        return Err(format!(
+            "invalid --mqtt-broker {}, should start with mqtt:// or mqtts://",
+            url
+        ));
+    };
+    let rest = rest.trim_end_matches('/');
+    let (host, port) = match rest.rsplit_once(':') {
+        Some((host, port)) => match port.parse::<u16>() {
+            Ok(port) => (host, port),
+            Err(_) => return Err(format!("invalid port {} in --mqtt-broker {}", port, url)),
+        },
+        None if tls => (rest, 8883),
+        None => (rest, 1883),
+    };
+    if host.is_empty() {
+        return Err(format!("no host in --mqtt-broker {}", url));
+    }
+    Ok(Broker {
+        host: host.to_string(),
+        port,
+        tls,
+    })
+}
+
+// how to log in to the broker, and which certificate authorities to trust
+// with TLS, the public ones when there is no --mqtt-ca-file
+pub struct Login {
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub ca_file: Option<PathBuf>,
+}
+
+trait Stream: Read + Write {}
+impl<T: Read + Write> Stream for T {}
+
+// a connection to a broker, for publishing wake messages instead of sending
+// magic packets, so a listener inside the LAN wakes the device
+pub struct Session {
+    stream: Box<dyn Stream>,
+    packet_id: u16,
+}
+
+impl Session {
+    pub fn connect(broker: &Broker, login: &Login) -> Result<Session, String> {
+        let address = (broker.host.as_str(), broker.port)
+            .to_socket_addrs()
+            .map_err(|err| format!("failed to resolve {}: {}", broker.host, err))?
+            .next()
+            .ok_or_else(|| format!("failed to resolve {}", broker.host))?;
+        let tcp = TcpStream::connect_timeout(&address, TIMEOUT)
+            .map_err(|err| format!("failed to connect to {}: {}", address, err))?;
+        tcp.set_read_timeout(Some(TIMEOUT)).map_err(|err| err.to_string())?;
+        tcp.set_write_timeout(Some(TIMEOUT)).map_err(|err| err.to_string())?;
+        let stream: Box<dyn Stream> = if broker.tls {
+            Box::new(tls_stream(tcp, &broker.host, login)?)
+        } else {
+            Box::new(tcp)
+        };
+
+        let mut session = Session {
+            stream,
+            packet_id: 0,
+        };
+        session.send_connect(login)?;
+        Ok(session)
+    }
+
+    // publish `payload` on `topic` and wait for the broker to confirm it
+    pub fn publish(&mut self, topic: &str, payload: &[u8]) -> Result<(), String> {
+        self.packet_id = self.packet_id.wrapping_add(1).max(1);
+        let mut body = Vec::new();
+        push_string(&mut body, topic.as_bytes());
+        body.extend_from_slice(&self.packet_id.to_be_bytes());
+        body.extend_from_slice(payload);
+        self.write_packet(PUBLISH_QOS_1, &body)?;
+
+        let (kind, body) = self.read_packet()?;
+        if kind & 0xF0 != PUBACK || body.len() < 2 {
+            return Err(format!("expected a PUBACK, got packet type {:#04x}", kind));
+        }
+        if body[..2] != self.packet_id.to_be_bytes() {
+            return Err("the broker confirmed another message".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn disconnect(mut self) {
+        // the messages are confirmed already, a failure here loses nothing
+        let _ = self.write_packet(DISCONNECT, &[]);
+    }
+
+    fn send_connect(&mut self, login: &Login) -> Result<(), String> {
+        let mut flags = 0x02; // clean session
+        if login.username.is_some() {
+            flags |= 0x80;
+        }
+        if login.password.is_some() {
+            flags |= 0x40;
+        }
+        let mut body = Vec::new();
+        push_string(&mut body, b"MQTT");
+        body.push(4); // protocol level 3.1.1
+        body.push(flags);
+        body.extend_from_slice(&60u16.to_be_bytes()); // keep alive seconds
+        push_string(&mut body, format!("wol-{}", std::process::id()).as_bytes());
+        if let Some(username) = &login.username {
+            push_string(&mut body, username.as_bytes());
+        }
+        if let Some(password) = &login.password {
+            push_string(&mut body, password.as_bytes());
+        }
+        self.write_packet(CONNECT, &body)?;
+
+        let (kind, body) = self.read_packet()?;
+        if kind != CONNACK || body.len() < 2 {
+            return Err(format!("expected a CONNACK, got packet type {:#04x}", kind));
+        }
+        match body[1] {
+            0 => Ok(()),
+            1 => Err("the broker doesn't support MQTT 3.1.1".to_string()),
+            2 => Err("the broker rejected the client ID".to_string()),
+            3 => Err("the broker is unavailable".to_string()),
+            4 => Err("the broker rejected the username or password".to_string()),
+            5 => Err("not authorized by the broker".to_string()),
+            code => Err(format!("the broker refused the connection with code {}", code)),
+        }
+    }
+
+    fn write_packet(&mut self, kind: u8, body: &[u8]) -> Result<(), String> {
+        let mut packet = vec![kind];
+        push_length(&mut packet, body.len());
+        packet.extend_from_slice(body);
+        self.stream
+            .write_all(&packet)
+            .and_then(|_| self.stream.flush())
+            .map_err(|err| format!("failed to send to the broker: {}", err))
+    }
+
+    fn read_packet(&mut self) -> Result<(u8, Vec<u8>), String> {
+        let read_error = |err: io::Error| format!("failed to read from the broker: {}", err);
+        let mut byte = [0u8];
+        self.stream.read_exact(&mut byte).map_err(read_error)?;
+        let kind = byte[0];
+        // the remaining length, 7 bits per byte, least significant first
+        let mut length = 0usize;
+        for shift in (0..28).step_by(7) {
+            self.stream.read_exact(&mut byte).map_err(read_error)?;
+            length |= usize::from(byte[0] & 0x7F) << shift;
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+        }
+        let mut body = vec![0u8; length];
+        self.stream.read_exact(&mut body).map_err(read_error)?;
+        Ok((kind, body))
+    }
+}
+
+fn tls_stream(
+    tcp: TcpStream,
+    host: &str,
+    login: &Login,
+) -> Result<rustls::StreamOwned<rustls::ClientConnection, TcpStream>, String> {
+    let mut roots = rustls::RootCertStore::empty();
+    match &login.ca_file {
+        Some(path) => {
+            let pem = fs::read(path)
+                .map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+            for cert in CertificateDer::pem_slice_iter(&pem) {
+                let cert = cert.map_err(|err| format!("invalid {}: {}", path.display(), err))?;
+                roots
+                    .add(cert)
+                    .map_err(|err| format!("invalid certificate in {}: {}", path.display(), err))?;
+            }
+            if roots.is_empty() {
+                return Err(format!("no certificate in {}", path.display()));
+            }
+        }
+        None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+    }
+    let config = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .map_err(|err| err.to_string())?
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    let name = ServerName::try_from(host.to_string())
+        .map_err(|err| format!("invalid TLS server name {}: {}", host, err))?;
+    let connection = rustls::ClientConnection::new(Arc::new(config), name)
+        .map_err(|err| format!("failed to start TLS: {}", err))?;
+    Ok(rustls::StreamOwned::new(connection, tcp))
+}
+
+// a UTF-8 string or binary field, prefixed by its 16 bit length
+fn push_string(buf: &mut Vec<u8>, value: &[u8]) {
+    buf.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    buf.extend_from_slice(value);
+}
+
+fn push_length(buf: &mut Vec<u8>, mut length: usize) {
+    loop {
+        let mut byte = (length & 0x7F) as u8;
+        length >>= 7;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        buf.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_brokers_with_default_ports() {
+        let broker = parse_broker("mqtt://broker.lan").unwrap();
+        assert_eq!((broker.host.as_str(), broker.port, broker.tls), ("broker.lan", 1883, false));
+        let broker = parse_broker("mqtts://broker.lan:8884/").unwrap();
+        assert_eq!((broker.host.as_str(), broker.port, broker.tls), ("broker.lan", 8884, true));
+        assert_eq!(parse_broker("mqtts://broker.lan").unwrap().port, 8883);
+        assert!(parse_broker("broker.lan").is_err());
+        assert!(parse_broker("mqtt://:1883").is_err());
+    }
+
+    #[test]
+    fn encodes_remaining_lengths() {
+        let encoded = |length| {
+            let mut buf = Vec::new();
+            push_length(&mut buf, length);
+            buf
+        };
+        assert_eq!(encoded(0), [0x00]);
+        assert_eq!(encoded(127), [0x7F]);
+        assert_eq!(encoded(128), [0x80, 0x01]);
+        assert_eq!(encoded(16_383), [0xFF, 0x7F]);
+        assert_eq!(encoded(16_384), [0x80, 0x80, 0x01]);
+    }
+}
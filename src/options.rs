@@ -0,0 +1,256 @@
+use ipnet::Ipv4Net;
+use std::error::Error;
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+use crate::packet::WOL_PORT;
+
+/// How magic packets are sent, built with [`WakeOptions::builder`].
+///
+/// ```
+/// use std::time::Duration;
+/// use wol::WakeOptions;
+///
+/// let options = WakeOptions::builder()
+///     .port(7)
+///     .port(9)
+///     .repeat(3)
+///     .delay(Duration::from_millis(500))
+///     .networks(["192.168.1.0/24".parse().unwrap()])
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(options.ports(), [7, 9]);
+/// assert_eq!(options.repeat(), 3);
+/// ```
+///
+/// With the `serde` feature, deserializing goes through the builder, so it
+/// fails with an [`OptionsError`] where [`WakeOptionsBuilder::build`] would.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "WakeOptionsBuilder")
+)]
+pub struct WakeOptions {
+    ports: Vec<u16>,
+    repeat: u32,
+    delay: Duration,
+    password: Option<Vec<u8>>,
+    networks: Vec<Ipv4Net>,
+    interface: Option<String>,
+    host: Option<Ipv4Addr>,
+}
+
+impl WakeOptions {
+    pub fn builder() -> WakeOptionsBuilder {
+        WakeOptionsBuilder::default()
+    }
+
+    /// The UDP ports every packet is sent to, sorted and without duplicates.
+    pub fn ports(&self) -> &[u16] {
+        &self.ports
+    }
+
+    /// How many times every packet is sent.
+    pub fn repeat(&self) -> u32 {
+        self.repeat
+    }
+
+    /// The pause between repeated packets.
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    /// The SecureOn password appended to the packet, 4 or 6 bytes.
+    pub fn password(&self) -> Option<&[u8]> {
+        self.password.as_deref()
+    }
+
+    /// The networks to broadcast on, empty to use every local network.
+    pub fn networks(&self) -> &[Ipv4Net] {
+        &self.networks
+    }
+
+    /// The interface whose networks are broadcast on.
+    pub fn interface(&self) -> Option<&str> {
+        self.interface.as_deref()
+    }
+
+    /// The address packets are unicast to instead of being broadcast.
+    pub fn host(&self) -> Option<Ipv4Addr> {
+        self.host
+    }
+}
+
+impl Default for WakeOptions {
+    fn default() -> WakeOptions {
+        WakeOptions::builder().build().unwrap()
+    }
+}
+
+/// Builds [`WakeOptions`], checking that the options fit together.
+///
+/// ```
+/// use wol::{OptionsError, WakeOptions};
+///
+/// let err = WakeOptions::builder()
+///     .host("192.168.1.20".parse().unwrap())
+///     .interface("eth0")
+///     .build()
+///     .unwrap_err();
+///
+/// assert_eq!(err, OptionsError::Conflict("host", "interface"));
+/// ```
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize), serde(default))]
+pub struct WakeOptionsBuilder {
+    ports: Vec<u16>,
+    repeat: Option<u32>,
+    delay: Duration,
+    password: Option<Vec<u8>>,
+    networks: Vec<Ipv4Net>,
+    interface: Option<String>,
+    host: Option<Ipv4Addr>,
+}
+
+impl WakeOptionsBuilder {
+    /// Adds a UDP port, port 9 is used when none is added.
+    pub fn port(mut self, port: u16) -> WakeOptionsBuilder {
+        self.ports.push(port);
+        self
+    }
+
+    /// Sends every packet `count` times, once by default.
+    pub fn repeat(mut self, count: u32) -> WakeOptionsBuilder {
+        self.repeat = Some(count);
+        self
+    }
+
+    /// Pauses for `delay` between repeated packets.
+    pub fn delay(mut self, delay: Duration) -> WakeOptionsBuilder {
+        self.delay = delay;
+        self
+    }
+
+    /// Appends a SecureOn password of 4 or 6 bytes to the packet.
+    pub fn password(mut self, password: impl Into<Vec<u8>>) -> WakeOptionsBuilder {
+        self.password = Some(password.into());
+        self
+    }
+
+    /// Adds networks to broadcast on.
+    pub fn networks(mut self, networks: impl IntoIterator<Item = Ipv4Net>) -> WakeOptionsBuilder {
+        self.networks.extend(networks);
+        self
+    }
+
+    /// Only broadcasts on the networks of the interface `name`.
+    pub fn interface(mut self, name: impl Into<String>) -> WakeOptionsBuilder {
+        self.interface = Some(name.into());
+        self
+    }
+
+    /// Unicasts packets to `ip` instead of broadcasting them.
+    pub fn host(mut self, ip: Ipv4Addr) -> WakeOptionsBuilder {
+        self.host = Some(ip);
+        self
+    }
+
+    pub fn build(self) -> Result<WakeOptions, OptionsError> {
+        if self.host.is_some() && !self.networks.is_empty() {
+            return Err(OptionsError::Conflict("host", "networks"));
+        }
+        if self.host.is_some() && self.interface.is_some() {
+            return Err(OptionsError::Conflict("host", "interface"));
+        }
+        if self.interface.is_some() && !self.networks.is_empty() {
+            return Err(OptionsError::Conflict("interface", "networks"));
+        }
+        if self.ports.contains(&0) {
+            return Err(OptionsError::ZeroPort);
+        }
+        if self.repeat == Some(0) {
+            return Err(OptionsError::ZeroRepeat);
+        }
+        if let Some(password) = &self.password {
+            if password.len() != 4 && password.len() != 6 {
+                return Err(OptionsError::PasswordLength(password.len()));
+            }
+        }
+
+        let mut ports = self.ports;
+        if ports.is_empty() {
+            ports.push(WOL_PORT);
+        }
+        ports.sort_unstable();
+        ports.dedup();
+
+        Ok(WakeOptions {
+            ports,
+            repeat: self.repeat.unwrap_or(1),
+            delay: self.delay,
+            password: self.password,
+            networks: self.networks,
+            interface: self.interface,
+            host: self.host,
+        })
+    }
+}
+
+impl TryFrom<WakeOptionsBuilder> for WakeOptions {
+    type Error = OptionsError;
+
+    fn try_from(builder: WakeOptionsBuilder) -> Result<WakeOptions, OptionsError> {
+        builder.build()
+    }
+}
+
+/// Why [`WakeOptionsBuilder::build`] rejected the options.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionsError {
+    /// Two options that can't be used together were both set.
+    Conflict(&'static str, &'static str),
+    /// Port 0 can't be sent to.
+    ZeroPort,
+    /// Packets must be sent at least once.
+    ZeroRepeat,
+    /// The SecureOn password has neither 4 nor 6 bytes.
+    PasswordLength(usize),
+}
+
+impl fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OptionsError::Conflict(a, b) => write!(f, "{} can't be combined with {}", a, b),
+            OptionsError::ZeroPort => write!(f, "port 0 is not a valid destination"),
+            OptionsError::ZeroRepeat => write!(f, "repeat count must be at least 1"),
+            OptionsError::PasswordLength(len) => write!(
+                f,
+                "invalid SecureOn password length {} (should be 4 or 6 bytes)",
+                len
+            ),
+        }
+    }
+}
+
+impl Error for OptionsError {}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserializing_checks_the_options() {
+        let options = WakeOptions::builder().port(7).repeat(2).build().unwrap();
+        let json = serde_json::to_string(&options).unwrap();
+        assert_eq!(serde_json::from_str::<WakeOptions>(&json).unwrap(), options);
+
+        let defaults: WakeOptions = serde_json::from_str("{}").unwrap();
+        assert_eq!(defaults, WakeOptions::default());
+
+        let err = serde_json::from_str::<WakeOptions>(r#"{"ports": [0]}"#).unwrap_err();
+        assert!(err.to_string().contains("port 0"));
+    }
+}
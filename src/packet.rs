@@ -0,0 +1,129 @@
+use core::fmt;
+
+use crate::mac::MacAddr;
+
+/// The UDP port magic packets are usually sent to, the discard port 9.
+pub const WOL_PORT: u16 = 9;
+
+/// The six `0xFF` bytes every magic packet starts with.
+pub const MAGIC_HEADER: [u8; 6] = [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+
+/// How many times the MAC address follows [`MAGIC_HEADER`].
+pub const MAC_REPETITIONS: usize = 16;
+
+/// The length of a magic packet without a SecureOn password, 102 bytes.
+pub const MAGIC_PACKET_LEN: usize = MAGIC_HEADER.len() * (1 + MAC_REPETITIONS);
+
+/// The buffer given to [`write_magic_packet`] can't hold the packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferTooSmall {
+    /// The length the buffer needs.
+    pub needed: usize,
+}
+
+impl fmt::Display for BufferTooSmall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "buffer too small for the magic packet, {} bytes are needed",
+            self.needed
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BufferTooSmall {}
+
+/// Writes the magic packet for `target_mac` to the start of `buf` and
+/// returns its length, [`MAGIC_PACKET_LEN`].
+///
+/// Nothing is allocated, so this is available without the `std` feature.
+///
+/// ```
+/// use wol::{write_magic_packet, MacAddr, MAGIC_PACKET_LEN};
+///
+/// let mut buf = [0u8; MAGIC_PACKET_LEN];
+/// let len = write_magic_packet(&MacAddr::new([0x00, 0x11, 0x22, 0x33, 0x44, 0x55]), &mut buf)
+///     .unwrap();
+///
+/// assert_eq!(len, 102);
+/// assert_eq!(buf[..6], [0xFF; 6]);
+/// assert_eq!(buf[96..], [0x00, 0x11, 0x22, 0x33, 0x44, 0x55]);
+/// ```
+pub fn write_magic_packet(target_mac: &MacAddr, buf: &mut [u8]) -> Result<usize, BufferTooSmall> {
+    let buf = buf.get_mut(..MAGIC_PACKET_LEN).ok_or(BufferTooSmall {
+        needed: MAGIC_PACKET_LEN,
+    })?;
+    let mac_bytes = target_mac.octets();
+
+    let (header, macs) = buf.split_at_mut(MAGIC_HEADER.len());
+    header.copy_from_slice(&MAGIC_HEADER);
+    for chunk in macs.chunks_exact_mut(mac_bytes.len()) {
+        chunk.copy_from_slice(&mac_bytes);
+    }
+
+    Ok(MAGIC_PACKET_LEN)
+}
+
+/// Like [`write_magic_packet`], followed by a SecureOn `password`, which is
+/// 4 or 6 bytes long.
+pub fn write_magic_packet_with_password(
+    target_mac: &MacAddr,
+    password: &[u8],
+    buf: &mut [u8],
+) -> Result<usize, BufferTooSmall> {
+    let len = MAGIC_PACKET_LEN + password.len();
+    if buf.len() < len {
+        return Err(BufferTooSmall { needed: len });
+    }
+    write_magic_packet(target_mac, buf)?;
+    buf[MAGIC_PACKET_LEN..len].copy_from_slice(password);
+    Ok(len)
+}
+
+/// Builds the magic packet for `target_mac`: 6 bytes of `0xFF` followed by
+/// the MAC address repeated 16 times, 102 bytes in total.
+///
+/// The packet is a `Vec` so variants such as a SecureOn password can grow it.
+#[cfg(feature = "std")]
+pub fn build_magic_packet(target_mac: &MacAddr) -> Vec<u8> {
+    let mut magic_packet = vec![0; MAGIC_PACKET_LEN];
+    write_magic_packet(target_mac, &mut magic_packet).unwrap();
+    magic_packet
+}
+
+/// Builds the magic packet for `target_mac` followed by a SecureOn
+/// `password`, which is 4 or 6 bytes long.
+#[cfg(feature = "std")]
+pub fn build_magic_packet_with_password(target_mac: &MacAddr, password: &[u8]) -> Vec<u8> {
+    let mut magic_packet = build_magic_packet(target_mac);
+    magic_packet.extend_from_slice(password);
+    magic_packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_buffers_are_rejected() {
+        let mac = MacAddr::new([0x00, 0x11, 0x22, 0x33, 0x44, 0x55]);
+
+        let mut buf = [0u8; MAGIC_PACKET_LEN - 1];
+        assert_eq!(
+            write_magic_packet(&mac, &mut buf),
+            Err(BufferTooSmall { needed: 102 })
+        );
+
+        let mut buf = [0u8; MAGIC_PACKET_LEN + 4];
+        assert_eq!(
+            write_magic_packet_with_password(&mac, &[1, 2, 3, 4, 5, 6], &mut buf),
+            Err(BufferTooSmall { needed: 108 })
+        );
+        assert_eq!(
+            write_magic_packet_with_password(&mac, &[1, 2, 3, 4], &mut buf),
+            Ok(106)
+        );
+        assert_eq!(buf[102..], [1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,152 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::net::SocketAddrV4;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// classic pcap, microsecond timestamps and Ethernet frames
+const PCAP_MAGIC: u32 = 0xa1b2_c3d4;
+const LINKTYPE_ETHERNET: u32 = 1;
+const SNAPLEN: u32 = 65535;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const IPPROTO_UDP: u8 = 17;
+
+// --write-pcap, every sent packet as the Ethernet frame that carries it. The
+// tool only sees the UDP payload, so the frame is rebuilt around it: the
+// source MAC address is zero, the destination one is the broadcast address
+// or zero for unicast, and the source IP address is 0.0.0.0
+pub struct PcapWriter {
+    out: BufWriter<File>,
+}
+
+impl PcapWriter {
+    pub fn create(path: &Path) -> io::Result<PcapWriter> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(&PCAP_MAGIC.to_le_bytes())?;
+        // version 2.4, UTC timestamps with no accuracy given
+        out.write_all(&2u16.to_le_bytes())?;
+        out.write_all(&4u16.to_le_bytes())?;
+        out.write_all(&0i32.to_le_bytes())?;
+        out.write_all(&0u32.to_le_bytes())?;
+        out.write_all(&SNAPLEN.to_le_bytes())?;
+        out.write_all(&LINKTYPE_ETHERNET.to_le_bytes())?;
+        Ok(PcapWriter { out })
+    }
+
+    pub fn write_udp(
+        &mut self,
+        source_port: u16,
+        destination: SocketAddrV4,
+        broadcast: bool,
+        payload: &[u8],
+    ) -> io::Result<()> {
+        let frame = udp_frame(source_port, destination, broadcast, payload);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        self.out.write_all(&(now.as_secs() as u32).to_le_bytes())?;
+        self.out.write_all(&now.subsec_micros().to_le_bytes())?;
+        // captured and original length, the frame is never cut
+        self.out.write_all(&(frame.len() as u32).to_le_bytes())?;
+        self.out.write_all(&(frame.len() as u32).to_le_bytes())?;
+        self.out.write_all(&frame)?;
+        self.out.flush()
+    }
+}
+
+fn udp_frame(
+    source_port: u16,
+    destination: SocketAddrV4,
+    broadcast: bool,
+    payload: &[u8],
+) -> Vec<u8> {
+    let source_ip = [0u8; 4];
+    let destination_ip = destination.ip().octets();
+    let udp_len = 8 + payload.len() as u16;
+    let ip_len = 20 + udp_len;
+
+    let mut frame = Vec::with_capacity(14 + ip_len as usize);
+    let destination_mac = if broadcast { [0xFF; 6] } else { [0; 6] };
+    frame.extend_from_slice(&destination_mac);
+    frame.extend_from_slice(&[0; 6]);
+    frame.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+
+    let mut ip_header = [0u8; 20];
+    // version 4, 5 words of header
+    ip_header[0] = 0x45;
+    ip_header[2..4].copy_from_slice(&ip_len.to_be_bytes());
+    // TTL
+    ip_header[8] = 64;
+    ip_header[9] = IPPROTO_UDP;
+    ip_header[12..16].copy_from_slice(&source_ip);
+    ip_header[16..20].copy_from_slice(&destination_ip);
+    let checksum = internet_checksum(&[&ip_header]);
+    ip_header[10..12].copy_from_slice(&checksum.to_be_bytes());
+    frame.extend_from_slice(&ip_header);
+
+    let mut udp_header = [0u8; 8];
+    udp_header[0..2].copy_from_slice(&source_port.to_be_bytes());
+    udp_header[2..4].copy_from_slice(&destination.port().to_be_bytes());
+    udp_header[4..6].copy_from_slice(&udp_len.to_be_bytes());
+    // the UDP checksum covers a pseudo header of the addresses, protocol
+    // and length
+    let mut pseudo_header = [0u8; 12];
+    pseudo_header[0..4].copy_from_slice(&source_ip);
+    pseudo_header[4..8].copy_from_slice(&destination_ip);
+    pseudo_header[9] = IPPROTO_UDP;
+    pseudo_header[10..12].copy_from_slice(&udp_len.to_be_bytes());
+    let checksum = match internet_checksum(&[&pseudo_header, &udp_header, payload]) {
+        // zero means no checksum, so a computed zero is sent as all ones
+        0 => 0xFFFF,
+        v => v,
+    };
+    udp_header[6..8].copy_from_slice(&checksum.to_be_bytes());
+    frame.extend_from_slice(&udp_header);
+    frame.extend_from_slice(payload);
+
+    frame
+}
+
+// the ones' complement of the ones' complement sum of 16 bit words, over
+// `parts` as if they were one buffer. Every part but the last has an even
+// length
+fn internet_checksum(parts: &[&[u8]]) -> u16 {
+    let mut sum: u32 = 0;
+    for part in parts {
+        for word in part.chunks(2) {
+            let high = u32::from(word[0]) << 8;
+            let low = word.get(1).copied().map(u32::from).unwrap_or(0);
+            sum += high | low;
+        }
+    }
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn frames_checksum_to_zero() {
+        let destination = SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 255), 9);
+        let payload = [0xFF; 7];
+        let frame = udp_frame(40000, destination, true, &payload);
+
+        assert_eq!(frame.len(), 14 + 20 + 8 + 7);
+        assert_eq!(frame[..6], [0xFF; 6]);
+        assert_eq!(frame[12..14], [0x08, 0x00]);
+        // a header with its checksum filled in sums to zero
+        assert_eq!(internet_checksum(&[&frame[14..34]]), 0);
+
+        let mut pseudo_header = [0u8; 12];
+        pseudo_header[4..8].copy_from_slice(&[192, 168, 1, 255]);
+        pseudo_header[9] = IPPROTO_UDP;
+        pseudo_header[10..12].copy_from_slice(&15u16.to_be_bytes());
+        assert_eq!(internet_checksum(&[&pseudo_header, &frame[34..]]), 0);
+    }
+}
@@ -0,0 +1,36 @@
+use std::net::Ipv4Addr;
+#[cfg(target_os = "linux")]
+use std::process::{Command, Stdio};
+
+// the outcome of pinging a broadcast address for --broadcast-only-reachable
+pub enum BroadcastPing {
+    Answered,
+    NoAnswer,
+    // ping is missing, not allowed or refuses broadcasts, so the network
+    // can't be checked
+    Unavailable(String),
+}
+
+// send one ICMP echo to `broadcast` with the system ping, which has the
+// privileges raw sockets need, and wait a second for any host to answer
+#[cfg(target_os = "linux")]
+pub fn ping_broadcast(broadcast: Ipv4Addr) -> BroadcastPing {
+    let output = Command::new("ping")
+        .args(["-b", "-c", "1", "-W", "1", "-q", &broadcast.to_string()])
+        .stdin(Stdio::null())
+        .output();
+    match output {
+        Ok(v) => match v.status.code() {
+            Some(0) => BroadcastPing::Answered,
+            // iputils ping exits with 1 when no reply came and 2 on errors
+            Some(1) => BroadcastPing::NoAnswer,
+            _ => BroadcastPing::Unavailable(String::from_utf8_lossy(&v.stderr).trim().to_string()),
+        },
+        Err(err) => BroadcastPing::Unavailable(format!("failed to run ping: {}", err)),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn ping_broadcast(_broadcast: Ipv4Addr) -> BroadcastPing {
+    BroadcastPing::Unavailable("broadcast pings are only supported on Linux".to_string())
+}
@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+// the pauses between `count` sends of the same packets, one fewer than the
+// sends. With backoff every pause is twice the previous one, starting at
+// `delay` and never longer than `max_delay`
+pub fn repeat_delays(
+    count: u32,
+    delay: Duration,
+    backoff: bool,
+    max_delay: Option<Duration>,
+) -> Vec<Duration> {
+    let cap = max_delay.unwrap_or(Duration::MAX);
+    let mut delays = Vec::new();
+    let mut next = delay.min(cap);
+    for _ in 1..count {
+        delays.push(next);
+        if backoff {
+            next = next.checked_mul(2).unwrap_or(Duration::MAX).min(cap);
+        }
+    }
+    delays
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn a_single_send_has_no_delays() {
+        assert!(repeat_delays(1, ms(100), true, None).is_empty());
+    }
+
+    #[test]
+    fn fixed_delay_without_backoff() {
+        assert_eq!(
+            repeat_delays(4, ms(100), false, None),
+            [ms(100), ms(100), ms(100)]
+        );
+    }
+
+    #[test]
+    fn backoff_doubles_the_delay() {
+        assert_eq!(
+            repeat_delays(5, ms(100), true, None),
+            [ms(100), ms(200), ms(400), ms(800)]
+        );
+    }
+
+    #[test]
+    fn backoff_stops_at_the_cap() {
+        assert_eq!(
+            repeat_delays(6, ms(100), true, Some(ms(500))),
+            [ms(100), ms(200), ms(400), ms(500), ms(500)]
+        );
+    }
+
+    #[test]
+    fn a_delay_above_the_cap_is_capped() {
+        assert_eq!(
+            repeat_delays(3, ms(800), true, Some(ms(500))),
+            [ms(500), ms(500)]
+        );
+    }
+
+    #[test]
+    fn backoff_saturates_instead_of_overflowing() {
+        let delays = repeat_delays(100, Duration::from_secs(1), true, None);
+
+        assert_eq!(delays.len(), 99);
+        assert_eq!(delays[98], Duration::MAX);
+    }
+}
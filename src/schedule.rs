@@ -0,0 +1,82 @@
+use chrono::{DateTime, Days, Local, NaiveDateTime, NaiveTime, TimeZone};
+use std::thread;
+use std::time::Duration;
+
+use crate::duration::parse_duration;
+use crate::signal;
+
+const DATETIME_FORMATS: [&str; 4] = [
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+];
+
+// parse the --at value: HH:MM (today, or tomorrow if already past), an ISO
+// timestamp, or a relative +30m
+pub fn parse_wake_time(s: &str) -> Result<DateTime<Local>, String> {
+    let now = Local::now();
+
+    if let Some(relative) = s.strip_prefix('+') {
+        let delay = chrono::Duration::from_std(parse_duration(relative)?)
+            .map_err(|_| format!("{:?} is too far in the future", s))?;
+        return Ok(now + delay);
+    }
+
+    let time =
+        NaiveTime::parse_from_str(s, "%H:%M").or_else(|_| NaiveTime::parse_from_str(s, "%H:%M:%S"));
+    if let Ok(time) = time {
+        let today = now.date_naive().and_time(time);
+        let wake_time = to_local(today)?;
+        if wake_time > now {
+            return Ok(wake_time);
+        }
+        return to_local(today + Days::new(1));
+    }
+
+    if let Ok(wake_time) = DateTime::parse_from_rfc3339(s) {
+        return in_future(wake_time.with_timezone(&Local), now);
+    }
+    for format in DATETIME_FORMATS {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(s, format) {
+            return in_future(to_local(naive)?, now);
+        }
+    }
+
+    Err(format!(
+        "invalid time {:?}, expected HH:MM, an ISO timestamp such as 2024-05-01T07:00, or a relative time such as +30m",
+        s
+    ))
+}
+
+fn to_local(naive: NaiveDateTime) -> Result<DateTime<Local>, String> {
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .ok_or_else(|| format!("{} does not exist in the local time zone", naive))
+}
+
+fn in_future(wake_time: DateTime<Local>, now: DateTime<Local>) -> Result<DateTime<Local>, String> {
+    if wake_time <= now {
+        return Err(format!(
+            "{} is in the past",
+            wake_time.format("%Y-%m-%d %H:%M:%S")
+        ));
+    }
+    Ok(wake_time)
+}
+
+// sleep until `wake_time`, returns false when interrupted by Ctrl-C. The
+// clock is checked on every tick, so a suspended host still wakes on time
+pub fn wait_until(wake_time: DateTime<Local>) -> bool {
+    loop {
+        if signal::interrupted() {
+            return false;
+        }
+        let remaining = match (wake_time - Local::now()).to_std() {
+            Ok(v) if !v.is_zero() => v,
+            _ => return true,
+        };
+        thread::sleep(remaining.min(Duration::from_millis(100)));
+    }
+}
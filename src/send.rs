@@ -0,0 +1,181 @@
+use ipnet::Ipv4Net;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use crate::error::WakeError;
+use crate::mac::MacAddr;
+use crate::packet::build_magic_packet;
+
+/// The outcome of sending a magic packet on a single network and port.
+///
+/// With the `serde` feature it is serialized as
+/// `{"network": "192.168.1.0/24", "port": 9, "ok": false, "error": "..."}`,
+/// with the error as its message and `null` when the send succeeded.
+#[derive(Debug)]
+pub struct NetworkResult {
+    pub network: Ipv4Net,
+    pub port: u16,
+    pub result: Result<(), WakeError>,
+    /// How long the send took.
+    pub elapsed: Duration,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for NetworkResult {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut result = serializer.serialize_struct("NetworkResult", 4)?;
+        result.serialize_field("network", &self.network)?;
+        result.serialize_field("port", &self.port)?;
+        result.serialize_field("ok", &self.result.is_ok())?;
+        result.serialize_field(
+            "error",
+            &self.result.as_ref().err().map(|err| err.to_string()),
+        )?;
+        result.end()
+    }
+}
+
+/// Binds the socket used for every send.
+///
+/// Broadcast is enabled once here, so a sandbox that forbids it is reported
+/// as [`WakeError::BroadcastNotPermitted`] before anything is sent.
+pub fn bind_socket() -> Result<UdpSocket, WakeError> {
+    let socket = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 0))?;
+    match socket.set_broadcast(true) {
+        Ok(_) => Ok(socket),
+        Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+            Err(WakeError::BroadcastNotPermitted(err))
+        }
+        Err(err) => Err(WakeError::Io(err)),
+    }
+}
+
+/// Binds a socket that can only unicast, broadcast is never enabled on it.
+pub fn bind_unicast_socket() -> Result<UdpSocket, WakeError> {
+    Ok(UdpSocket::bind(SocketAddr::new(
+        IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)),
+        0,
+    ))?)
+}
+
+/// Binds a broadcast socket like [`bind_socket`] whose packets only leave
+/// through the interface `device`, e.g. `eth0`, with `SO_BINDTODEVICE`.
+///
+/// Otherwise the routing table picks the interface, which is not always the
+/// one of the network a broadcast is meant for. This is only available on
+/// Linux and usually needs `CAP_NET_RAW`.
+#[cfg(target_os = "linux")]
+pub fn bind_socket_to_device(device: &str) -> Result<UdpSocket, WakeError> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.bind_device(Some(device.as_bytes()))?;
+    socket.set_broadcast(true)?;
+    socket.bind(&SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 0).into())?;
+    Ok(socket.into())
+}
+
+/// Binds a broadcast socket like [`bind_socket`] whose packets only leave
+/// through the interface `device`, which is only available on Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn bind_socket_to_device(_device: &str) -> Result<UdpSocket, WakeError> {
+    Err(WakeError::Io(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "binding a socket to an interface is only supported on Linux",
+    )))
+}
+
+/// Sends the magic packet for `mac_address` on every network and port.
+///
+/// A failure on one network does not stop the others, so each result is
+/// returned with the network it was sent on.
+pub fn send_wol_packet(
+    socket: &UdpSocket,
+    mac_address: &MacAddr,
+    networks: &[Ipv4Net],
+    ports: &[u16],
+) -> Vec<NetworkResult> {
+    networks
+        .iter()
+        .flat_map(|broadcast_net| {
+            ports
+                .iter()
+                .map(move |port| send_network(socket, mac_address, broadcast_net, *port))
+        })
+        .collect()
+}
+
+/// Sends the magic packet on a single network and port, timing the send.
+pub fn send_network(
+    socket: &UdpSocket,
+    mac_address: &MacAddr,
+    network: &Ipv4Net,
+    port: u16,
+) -> NetworkResult {
+    send_network_to(socket, mac_address, network, network.broadcast(), port)
+}
+
+/// Like [`send_network`], but sends to `destination` instead of the
+/// network's computed broadcast address, for networks whose broadcast
+/// address is nonstandard.
+pub fn send_network_to(
+    socket: &UdpSocket,
+    mac_address: &MacAddr,
+    network: &Ipv4Net,
+    destination: Ipv4Addr,
+    port: u16,
+) -> NetworkResult {
+    let magic_packet = build_magic_packet(mac_address);
+    send_packet(socket, &magic_packet, network, destination, port)
+}
+
+/// Sends an already built `packet` to `destination`, timing the send. The
+/// result records `network` as the network it was sent on.
+pub fn send_packet(
+    socket: &UdpSocket,
+    packet: &[u8],
+    network: &Ipv4Net,
+    destination: Ipv4Addr,
+    port: u16,
+) -> NetworkResult {
+    let send_start = Instant::now();
+    let result = socket
+        .send_to(packet, SocketAddr::new(destination.into(), port))
+        .map(|_| ())
+        .map_err(WakeError::from);
+    NetworkResult {
+        network: *network,
+        port,
+        result,
+        elapsed: send_start.elapsed(),
+    }
+}
+
+/// Sends the magic packet to the broadcast address of `ip_net`.
+pub fn send_magic_packet(
+    socket: &UdpSocket,
+    target_mac: &MacAddr,
+    ip_net: &Ipv4Net,
+    port: u16,
+) -> Result<(), WakeError> {
+    send_magic_packet_to(socket, target_mac, ip_net.broadcast(), port)
+}
+
+/// Sends the magic packet to `destination`, a broadcast or unicast address.
+pub fn send_magic_packet_to(
+    socket: &UdpSocket,
+    target_mac: &MacAddr,
+    destination: Ipv4Addr,
+    port: u16,
+) -> Result<(), WakeError> {
+    let magic_packet = build_magic_packet(target_mac);
+
+    let dest = SocketAddr::new(destination.into(), port);
+
+    socket.send_to(&magic_packet, dest)?;
+
+    Ok(())
+}
@@ -0,0 +1,283 @@
+use ipnet::Ipv4Net;
+use std::collections::HashSet;
+use std::net::UdpSocket;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::WakeError;
+use crate::mac::MacAddr;
+use crate::options::WakeOptions;
+use crate::send::{bind_socket, bind_unicast_socket, NetworkResult};
+use crate::target::Target;
+use crate::wake::{resolve_networks, send_all};
+
+/// A sender that binds its socket and finds its networks once, built with
+/// [`Wol::builder`], for sending many wakes with the same [`WakeOptions`].
+///
+/// Its sends go through the same loop as [`wake_all`](crate::wake_all), only
+/// the socket and the networks are kept between wakes. The `wol` command
+/// line tool doesn't use it.
+///
+/// ```
+/// use wol::{MacAddr, WakeOptions, Wol};
+///
+/// let options = WakeOptions::builder()
+///     .host("127.0.0.1".parse().unwrap())
+///     .port(40009)
+///     .build()
+///     .unwrap();
+/// let wol = Wol::builder().options(options).build().unwrap();
+///
+/// let mac_address: MacAddr = "00:11:22:33:44:55".parse().unwrap();
+/// let results = wol.wake(&mac_address);
+/// assert_eq!(results.len(), 1);
+/// assert!(results[0].result.is_ok());
+/// ```
+#[derive(Debug)]
+pub struct Wol {
+    socket: UdpSocket,
+    options: WakeOptions,
+    networks: Vec<Ipv4Net>,
+    threads: usize,
+}
+
+/// The results of waking one MAC address with [`Wol::wake_many`].
+///
+/// With the `serde` feature it is serialized as `{"mac": ..., "results": [...]}`
+/// with the results serialized like [`NetworkResult`].
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct WakeReport {
+    pub mac: MacAddr,
+    /// One result per network and port, `Ok` when any repeat was sent.
+    pub results: Vec<NetworkResult>,
+}
+
+/// The totals of a [`Wol::wake_many`] call, counted from its reports once
+/// every sending thread is done.
+#[derive(Debug)]
+pub struct WakeSummary {
+    /// One report per distinct MAC address, in the order they were given.
+    pub reports: Vec<WakeReport>,
+    /// Sends that succeeded, one per MAC address, network and port.
+    pub sent: usize,
+    /// Sends that failed on every repeat.
+    pub failed: usize,
+    /// MAC addresses given more than once, which are only woken once.
+    pub skipped: usize,
+    /// The number of distinct networks and unicast addresses sent to.
+    pub networks: usize,
+    pub duration: Duration,
+}
+
+impl Wol {
+    pub fn builder() -> WolBuilder {
+        WolBuilder::default()
+    }
+
+    /// The options every wake is sent with.
+    pub fn options(&self) -> &WakeOptions {
+        &self.options
+    }
+
+    /// The networks every wake is broadcast on, empty when
+    /// [`WakeOptions::host`] is set.
+    pub fn networks(&self) -> &[Ipv4Net] {
+        &self.networks
+    }
+
+    /// Sends the magic packet for `mac_address`, returning one result per
+    /// network and port, which is `Ok` when any repeat was sent.
+    pub fn wake(&self, mac_address: &MacAddr) -> Vec<NetworkResult> {
+        self.wake_many(std::slice::from_ref(mac_address))
+            .reports
+            .swap_remove(0)
+            .results
+    }
+
+    /// Like [`Wol::wake`] for every MAC address, split over
+    /// [`WolBuilder::threads`] threads, with the totals.
+    pub fn wake_many(&self, mac_addresses: &[MacAddr]) -> WakeSummary {
+        let start = Instant::now();
+        let mut seen = HashSet::new();
+        let targets: Vec<Target> = mac_addresses
+            .iter()
+            .filter(|mac| seen.insert(**mac))
+            .copied()
+            .map(Target::new)
+            .collect();
+
+        let chunk_len = targets.len().div_ceil(self.threads).max(1);
+        let reports: Vec<WakeReport> = thread::scope(|scope| {
+            let handles: Vec<_> = targets
+                .chunks(chunk_len)
+                .map(|chunk| scope.spawn(move || self.reports(chunk)))
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        let results = reports.iter().flat_map(|report| &report.results);
+        let failed = results
+            .clone()
+            .filter(|net_result| net_result.result.is_err())
+            .count();
+        let networks: HashSet<Ipv4Net> = results
+            .clone()
+            .map(|net_result| net_result.network)
+            .collect();
+        WakeSummary {
+            sent: results.count() - failed,
+            failed,
+            skipped: mac_addresses.len() - targets.len(),
+            networks: networks.len(),
+            duration: start.elapsed(),
+            reports,
+        }
+    }
+
+    // send to `targets` on this thread, every target gets the same number of
+    // results
+    fn reports(&self, targets: &[Target]) -> Vec<WakeReport> {
+        let mut results = send_all(
+            &self.socket,
+            targets,
+            &self.networks,
+            &self.options,
+            &mut |_| {},
+            Instant::now(),
+        )
+        .into_iter();
+        let per_target = results.len() / targets.len().max(1);
+        targets
+            .iter()
+            .map(|target| WakeReport {
+                mac: target.mac,
+                results: results.by_ref().take(per_target).collect(),
+            })
+            .collect()
+    }
+}
+
+/// Builds a [`Wol`] sender.
+#[derive(Debug, Default)]
+pub struct WolBuilder {
+    options: Option<WakeOptions>,
+    socket: Option<UdpSocket>,
+    threads: Option<usize>,
+}
+
+impl WolBuilder {
+    /// Sends with `options`, the default ones broadcast on every local
+    /// network.
+    pub fn options(mut self, options: WakeOptions) -> WolBuilder {
+        self.options = Some(options);
+        self
+    }
+
+    /// Sends through `socket`, which must have broadcast enabled to
+    /// broadcast, instead of binding one.
+    pub fn socket(mut self, socket: UdpSocket) -> WolBuilder {
+        self.socket = Some(socket);
+        self
+    }
+
+    /// Sends from up to `threads` threads in [`Wol::wake_many`], one by
+    /// default. A count of zero is taken as one.
+    pub fn threads(mut self, threads: usize) -> WolBuilder {
+        self.threads = Some(threads.max(1));
+        self
+    }
+
+    /// Finds the networks to broadcast on and binds the socket, a unicast
+    /// one when [`WakeOptions::host`] is set.
+    pub fn build(self) -> Result<Wol, WakeError> {
+        let options = self.options.unwrap_or_default();
+        let networks = match options.host() {
+            Some(_) => Vec::new(),
+            None => resolve_networks(&options)?,
+        };
+        let socket = match self.socket {
+            Some(v) => v,
+            None if options.host().is_some() => bind_unicast_socket()?,
+            None => bind_socket()?,
+        };
+        Ok(Wol {
+            socket,
+            options,
+            networks,
+            threads: self.threads.unwrap_or(1),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concurrent_sends_add_up() {
+        let options = WakeOptions::builder()
+            .host("127.0.0.1".parse().unwrap())
+            .port(40007)
+            .port(40009)
+            .build()
+            .unwrap();
+        let wol = Wol::builder().options(options).threads(8).build().unwrap();
+        let mut mac_addresses: Vec<MacAddr> = (0..100)
+            .map(|i| MacAddr::new([0x02, 0, 0, 0, 0, i]))
+            .collect();
+        mac_addresses.push(mac_addresses[0]);
+
+        let summary = wol.wake_many(&mac_addresses);
+        assert_eq!(summary.sent, 200);
+        assert_eq!(summary.failed, 0);
+        assert_eq!(summary.skipped, 1);
+        assert_eq!(summary.networks, 1);
+        assert_eq!(summary.reports.len(), 100);
+        for (i, report) in summary.reports.iter().enumerate() {
+            assert_eq!(report.mac, mac_addresses[i]);
+            assert_eq!(report.results.len(), 2);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn reports_serialize_errors_as_their_message() {
+        let report = WakeReport {
+            mac: MacAddr::new([0x02, 0, 0, 0, 0, 1]),
+            results: vec![
+                NetworkResult {
+                    network: "192.168.1.0/24".parse().unwrap(),
+                    port: 9,
+                    result: Ok(()),
+                    elapsed: Duration::from_millis(1),
+                },
+                NetworkResult {
+                    network: "10.0.0.0/8".parse().unwrap(),
+                    port: 7,
+                    result: Err(WakeError::NoNetworks),
+                    elapsed: Duration::from_millis(1),
+                },
+            ],
+        };
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "mac": "02:00:00:00:00:01",
+                "results": [
+                    {"network": "192.168.1.0/24", "port": 9, "ok": true, "error": null},
+                    {
+                        "network": "10.0.0.0/8",
+                        "port": 7,
+                        "ok": false,
+                        "error": "no network to broadcast on was found",
+                    },
+                ],
+            })
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use std::env;
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+
+use crate::signal;
+
+// $XDG_RUNTIME_DIR/wol.sock, falling back to the temp directory
+pub fn default_path() -> PathBuf {
+    env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(env::temp_dir)
+        .join("wol.sock")
+}
+
+// listen on the Unix socket at `path` and answer every line a client sends
+// with what `on_line` returns for it, until Ctrl-C or `deadline`. Every
+// client is read on its own thread, so one that keeps its connection open
+// doesn't hold up the others, but `on_line` is called for one line at a time
+#[cfg(unix)]
+pub fn serve(
+    path: &Path,
+    deadline: Option<Instant>,
+    on_line: impl FnMut(&str) -> Option<String> + Send,
+) -> io::Result<()> {
+    // a socket file left behind by a server that was killed is replaced, one
+    // that still answers is not
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(io::Error::new(
+                ErrorKind::AddrInUse,
+                "another server is listening on it",
+            ));
+        }
+        std::fs::remove_file(path)?;
+    }
+    let listener = bind_private(path)?;
+    listener.set_nonblocking(true)?;
+    signal::install();
+
+    let tick = Duration::from_millis(100);
+    let stopped = || signal::interrupted() || deadline.is_some_and(|d| Instant::now() >= d);
+    let on_line = Mutex::new(on_line);
+    // the scope waits for the clients, which notice `stopped` within a tick
+    let served = thread::scope(|scope| loop {
+        if stopped() {
+            break Ok(());
+        }
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                thread::sleep(tick);
+                continue;
+            }
+            Err(err) => break Err(err),
+        };
+        let (stopped, on_line) = (&stopped, &on_line);
+        scope.spawn(move || {
+            if let Err(err) = serve_client(stream, tick, stopped, on_line) {
+                println!("Warning: a client connection failed: {}", err);
+            }
+        });
+    });
+    let _ = std::fs::remove_file(path);
+    served
+}
+
+// anyone who can connect can wake devices, so only the user running the
+// server may. The socket is bound in a directory only that user can enter
+// and moved to `path` once its mode is set, so no one can connect before
+#[cfg(unix)]
+fn bind_private(path: &Path) -> io::Result<UnixListener> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let dir = path.with_file_name(format!(".{}.{}", name, std::process::id()));
+    std::fs::DirBuilder::new().mode(0o700).create(&dir)?;
+    let staged = dir.join("sock");
+    let bound = UnixListener::bind(&staged).and_then(|listener| {
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600))?;
+        std::fs::rename(&staged, path)?;
+        Ok(listener)
+    });
+    let _ = std::fs::remove_file(&staged);
+    let _ = std::fs::remove_dir(&dir);
+    bound
+}
+
+#[cfg(unix)]
+fn serve_client(
+    stream: UnixStream,
+    tick: Duration,
+    stopped: &impl Fn() -> bool,
+    on_line: &Mutex<impl FnMut(&str) -> Option<String>>,
+) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    // reads time out now and then to notice Ctrl-C, a partly read line stays
+    // in `line` until the rest comes
+    stream.set_read_timeout(Some(tick))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    while !stopped() {
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => return Ok(()),
+            Ok(_) if line.ends_with(b"\n") => {}
+            Ok(_) => continue,
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                continue;
+            }
+            Err(err) => return Err(err),
+        }
+        let text = String::from_utf8_lossy(&line).trim().to_string();
+        line.clear();
+        let reply = (on_line.lock().unwrap())(&text);
+        if let Some(reply) = reply {
+            writer.write_all(format!("{}\n", reply).as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+// send `lines` to the server listening at `path` and return its replies,
+// once it has answered them all
+#[cfg(unix)]
+pub fn send(path: &Path, lines: &[String]) -> io::Result<Vec<String>> {
+    let mut stream = UnixStream::connect(path)?;
+    for line in lines {
+        stream.write_all(format!("{}\n", line).as_bytes())?;
+    }
+    stream.shutdown(std::net::Shutdown::Write)?;
+    BufReader::new(stream).lines().collect()
+}
+
+#[cfg(not(unix))]
+pub fn serve(
+    _path: &Path,
+    _deadline: Option<Instant>,
+    _on_line: impl FnMut(&str) -> Option<String> + Send,
+) -> io::Result<()> {
+    Err(io::Error::new(
+        ErrorKind::Unsupported,
+        "the server needs Unix sockets",
+    ))
+}
+
+#[cfg(not(unix))]
+pub fn send(_path: &Path, _lines: &[String]) -> io::Result<Vec<String>> {
+    Err(io::Error::new(
+        ErrorKind::Unsupported,
+        "the server needs Unix sockets",
+    ))
+}
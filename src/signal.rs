@@ -0,0 +1,30 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// catch Ctrl-C so long waits can stop cleanly instead of the process being
+// killed, the waits poll `interrupted`
+pub fn install() {
+    let _ = ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst));
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+// sleep for `duration`, returns false when interrupted by Ctrl-C
+pub fn sleep(duration: Duration) -> bool {
+    let end = Instant::now() + duration;
+    loop {
+        if interrupted() {
+            return false;
+        }
+        let remaining = end.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return true;
+        }
+        thread::sleep(remaining.min(Duration::from_millis(100)));
+    }
+}
@@ -0,0 +1,31 @@
+use std::net::Ipv4Addr;
+
+use crate::mac::MacAddr;
+
+/// A device to wake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Target {
+    pub mac: MacAddr,
+    /// The device's own address. When set, the packet is sent to it by
+    /// unicast instead of being broadcast.
+    pub ip: Option<Ipv4Addr>,
+    /// A name for the device, e.g. its hostname.
+    pub name: Option<String>,
+}
+
+impl Target {
+    pub fn new(mac: MacAddr) -> Target {
+        Target {
+            mac,
+            ip: None,
+            name: None,
+        }
+    }
+}
+
+impl From<MacAddr> for Target {
+    fn from(mac: MacAddr) -> Target {
+        Target::new(mac)
+    }
+}
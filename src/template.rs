@@ -0,0 +1,149 @@
+use std::fmt;
+use std::str::FromStr;
+
+const FIELD_NAMES: [&str; 8] = [
+    "mac",
+    "name",
+    "network",
+    "broadcast",
+    "port",
+    "result",
+    "time",
+    "run_id",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Mac,
+    Name,
+    Network,
+    Broadcast,
+    Port,
+    Result,
+    Time,
+    RunId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Field(Field),
+}
+
+// a line printed for every send, e.g. "{mac} {network} {result}", with {{
+// and }} for literal braces
+#[derive(Debug, Clone)]
+pub struct Template(Vec<Part>);
+
+// the values substituted into a template for one send
+pub struct TemplateValues<'a> {
+    pub mac: &'a str,
+    pub name: &'a str,
+    pub network: &'a str,
+    pub broadcast: &'a str,
+    pub port: u16,
+    pub result: &'a str,
+    pub time: &'a str,
+    pub run_id: &'a str,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    UnknownPlaceholder(String),
+    Unclosed,
+    UnmatchedBrace,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateError::UnknownPlaceholder(name) => write!(
+                f,
+                "unknown placeholder {{{}}} in template, expected one of {{{}}}",
+                name,
+                FIELD_NAMES.join("}, {")
+            ),
+            TemplateError::Unclosed => write!(f, "unclosed {{ in template"),
+            TemplateError::UnmatchedBrace => {
+                write!(f, "unmatched }} in template, use }}}} for a literal brace")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+impl FromStr for Template {
+    type Err = TemplateError;
+
+    fn from_str(s: &str) -> Result<Template, TemplateError> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = s.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '}' => return Err(TemplateError::UnmatchedBrace),
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(TemplateError::Unclosed),
+                        }
+                    }
+                    let field = match name.as_str() {
+                        "mac" => Field::Mac,
+                        "name" => Field::Name,
+                        "network" => Field::Network,
+                        "broadcast" => Field::Broadcast,
+                        "port" => Field::Port,
+                        "result" => Field::Result,
+                        "time" => Field::Time,
+                        "run_id" => Field::RunId,
+                        _ => return Err(TemplateError::UnknownPlaceholder(name)),
+                    };
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Field(field));
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+
+        Ok(Template(parts))
+    }
+}
+
+impl Template {
+    pub fn render(&self, values: &TemplateValues) -> String {
+        let mut line = String::new();
+        for part in &self.0 {
+            match part {
+                Part::Text(text) => line.push_str(text),
+                Part::Field(Field::Mac) => line.push_str(values.mac),
+                Part::Field(Field::Name) => line.push_str(values.name),
+                Part::Field(Field::Network) => line.push_str(values.network),
+                Part::Field(Field::Broadcast) => line.push_str(values.broadcast),
+                Part::Field(Field::Port) => line.push_str(&values.port.to_string()),
+                Part::Field(Field::Result) => line.push_str(values.result),
+                Part::Field(Field::Time) => line.push_str(values.time),
+                Part::Field(Field::RunId) => line.push_str(values.run_id),
+            }
+        }
+        line
+    }
+}
@@ -0,0 +1,132 @@
+//! Async versions of [`wake_all`](crate::wake_all) and
+//! [`Wol::wake`](crate::Wol::wake) on a tokio `UdpSocket`, enabled by the
+//! `tokio` feature.
+//!
+//! The packets, where they go and the order of the sends and repeats come
+//! from the same code as the blocking API, only the sends and the delay
+//! between repeats are awaited, so no runtime thread is blocked while waking.
+
+use ipnet::Ipv4Net;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Instant;
+
+use ::tokio::net::UdpSocket;
+
+use crate::error::WakeError;
+use crate::mac::MacAddr;
+use crate::options::WakeOptions;
+use crate::send::{bind_socket, NetworkResult};
+use crate::target::Target;
+use crate::wake::{
+    broadcast_networks, finished, plan_sends, record_send, send_steps, started, WakeEvent,
+};
+
+/// Wakes every target as described by `options` like
+/// [`wake_all`](crate::wake_all), awaiting the sends and the
+/// [`WakeOptions::delay`] between repeats.
+///
+/// ```
+/// use wol::{MacAddr, Target, WakeOptions};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let mut target = Target::new("00:11:22:33:44:55".parse::<MacAddr>().unwrap());
+/// target.ip = Some("127.0.0.1".parse().unwrap());
+/// let options = WakeOptions::builder().port(40009).repeat(2).build().unwrap();
+///
+/// let results = wol::tokio::wake_all(&[target], &options, &mut |_| {})
+///     .await
+///     .unwrap();
+/// assert_eq!(results.len(), 1);
+/// assert!(results[0].result.is_ok());
+/// # }
+/// ```
+pub async fn wake_all(
+    targets: &[Target],
+    options: &WakeOptions,
+    on_event: &mut impl FnMut(WakeEvent),
+) -> Result<Vec<NetworkResult>, WakeError> {
+    let start = Instant::now();
+    let networks = broadcast_networks(targets, options)?;
+    let socket = bind_async_socket()?;
+
+    let sends = plan_sends(targets, &networks, options);
+    on_event(started(&sends, options));
+    let mut results = Vec::new();
+    for step in send_steps(targets, &sends, options.ports(), options.repeat()) {
+        if step.pauses_before() {
+            ::tokio::time::sleep(options.delay()).await;
+        }
+        let network = step.network;
+        let result = send_packet(
+            &socket,
+            step.packet,
+            network,
+            network.broadcast(),
+            step.port,
+        )
+        .await;
+        record_send(&mut results, &step, result, on_event);
+    }
+    on_event(finished(&results, start));
+    Ok(results)
+}
+
+/// Sends the magic packet for `mac_address` like [`Wol::wake`](crate::Wol::wake),
+/// returning one result per network and port, which is `Ok` when any repeat
+/// was sent. The socket is bound and the networks are found for this wake.
+///
+/// ```
+/// use wol::{MacAddr, WakeOptions};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let options = WakeOptions::builder()
+///     .host("127.0.0.1".parse().unwrap())
+///     .port(40009)
+///     .build()
+///     .unwrap();
+/// let mac_address: MacAddr = "00:11:22:33:44:55".parse().unwrap();
+///
+/// let results = wol::tokio::wake(&mac_address, &options).await.unwrap();
+/// assert_eq!(results.len(), 1);
+/// assert!(results[0].result.is_ok());
+/// # }
+/// ```
+pub async fn wake(
+    mac_address: &MacAddr,
+    options: &WakeOptions,
+) -> Result<Vec<NetworkResult>, WakeError> {
+    wake_all(&[Target::new(*mac_address)], options, &mut |_| {}).await
+}
+
+/// Binds a broadcast socket like [`bind_socket`] and hands it to the tokio
+/// runtime the caller is in.
+pub fn bind_async_socket() -> Result<UdpSocket, WakeError> {
+    let socket = bind_socket()?;
+    socket.set_nonblocking(true)?;
+    Ok(UdpSocket::from_std(socket)?)
+}
+
+/// Sends an already built `packet` to `destination` like
+/// [`send_packet`](crate::send_packet), awaiting the send.
+pub async fn send_packet(
+    socket: &UdpSocket,
+    packet: &[u8],
+    network: &Ipv4Net,
+    destination: Ipv4Addr,
+    port: u16,
+) -> NetworkResult {
+    let send_start = Instant::now();
+    let result = socket
+        .send_to(packet, SocketAddr::new(destination.into(), port))
+        .await
+        .map(|_| ())
+        .map_err(WakeError::from);
+    NetworkResult {
+        network: *network,
+        port,
+        result,
+        elapsed: send_start.elapsed(),
+    }
+}
@@ -0,0 +1,319 @@
+use ipnet::Ipv4Net;
+use std::net::UdpSocket;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::WakeError;
+use crate::interfaces::{get_local_ip_nets, InterfaceAddr, InterfaceProvider, SystemInterfaces};
+use crate::options::WakeOptions;
+use crate::packet::{build_magic_packet, build_magic_packet_with_password};
+use crate::send::{bind_socket, send_packet, NetworkResult};
+use crate::target::Target;
+
+/// Progress reported by [`wake_all`].
+#[derive(Debug)]
+pub enum WakeEvent<'a> {
+    /// Sending is about to start.
+    Started {
+        /// The number of targets.
+        targets: usize,
+        /// The number of sends, one per target, network and port, not
+        /// counting repeats.
+        sends: usize,
+    },
+    /// A single packet was sent, or failed to be.
+    Sent {
+        target: &'a Target,
+        result: &'a NetworkResult,
+        /// 1 for the first send, up to [`WakeOptions::repeat`].
+        attempt: u32,
+    },
+    /// Every packet was sent.
+    Finished {
+        /// Sends that succeeded at least once.
+        sent: usize,
+        /// Sends that failed on every attempt.
+        failed: usize,
+        elapsed: Duration,
+    },
+}
+
+/// Wakes every target as described by `options`, calling `on_event` as
+/// sending goes on.
+///
+/// Targets with an IP address, and every target when
+/// [`WakeOptions::host`] is set, are sent to by unicast. The others are
+/// broadcast on [`WakeOptions::networks`], the networks of
+/// [`WakeOptions::interface`], or every local network. Returns one result
+/// per target, network and port, in target order, which is `Ok` when any
+/// repeat was sent.
+///
+/// ```no_run
+/// use wol::{wake_all, MacAddr, Target, WakeEvent, WakeOptions};
+///
+/// let targets = [Target::new("00:11:22:33:44:55".parse::<MacAddr>().unwrap())];
+/// let options = WakeOptions::builder().repeat(3).build().unwrap();
+///
+/// wake_all(&targets, &options, &mut |event| match event {
+///     WakeEvent::Sent { target, result, .. } => {
+///         println!("{} on {}: {:?}", target.mac, result.network, result.result)
+///     }
+///     WakeEvent::Finished { sent, failed, .. } => println!("{} sent, {} failed", sent, failed),
+///     _ => {}
+/// })
+/// .unwrap();
+/// ```
+pub fn wake_all(
+    targets: &[Target],
+    options: &WakeOptions,
+    on_event: &mut impl FnMut(WakeEvent),
+) -> Result<Vec<NetworkResult>, WakeError> {
+    let start = Instant::now();
+    let networks = broadcast_networks(targets, options)?;
+    let socket = bind_socket()?;
+    Ok(send_all(
+        &socket, targets, &networks, options, on_event, start,
+    ))
+}
+
+// the networks to broadcast on, none when every target is sent to by unicast
+pub(crate) fn broadcast_networks(
+    targets: &[Target],
+    options: &WakeOptions,
+) -> Result<Vec<Ipv4Net>, WakeError> {
+    let needs_broadcast =
+        options.host().is_none() && targets.iter().any(|target| target.ip.is_none());
+    if needs_broadcast {
+        resolve_networks(options)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+// the networks to broadcast on, the listed ones, those of the interface, or
+// every local network
+pub(crate) fn resolve_networks(options: &WakeOptions) -> Result<Vec<Ipv4Net>, WakeError> {
+    let networks = if !options.networks().is_empty() {
+        options.networks().to_vec()
+    } else if let Some(name) = options.interface() {
+        let mut ip_nets: Vec<Ipv4Net> = SystemInterfaces
+            .interfaces()?
+            .iter()
+            .filter(|if_addr| if_addr.name == name)
+            .map(InterfaceAddr::network)
+            .collect();
+        ip_nets.sort();
+        ip_nets
+    } else {
+        get_local_ip_nets()?
+    };
+    if networks.is_empty() {
+        return Err(WakeError::NoNetworks);
+    }
+    Ok(networks)
+}
+
+// send to every target with its repeats on an already bound socket, the
+// targets without an IP address on `networks`
+pub(crate) fn send_all(
+    socket: &UdpSocket,
+    targets: &[Target],
+    networks: &[Ipv4Net],
+    options: &WakeOptions,
+    on_event: &mut impl FnMut(WakeEvent),
+    start: Instant,
+) -> Vec<NetworkResult> {
+    let sends = plan_sends(targets, networks, options);
+    on_event(started(&sends, options));
+    let mut results = Vec::new();
+    for step in send_steps(targets, &sends, options.ports(), options.repeat()) {
+        if step.pauses_before() {
+            thread::sleep(options.delay());
+        }
+        let network = step.network;
+        let result = send_packet(socket, step.packet, network, network.broadcast(), step.port);
+        record_send(&mut results, &step, result, on_event);
+    }
+    on_event(finished(&results, start));
+    results
+}
+
+// where every target's packet goes and the packet itself, shared by the
+// blocking and the async senders. A unicast target is sent to its own
+// address as a /32 network
+pub(crate) fn plan_sends(
+    targets: &[Target],
+    networks: &[Ipv4Net],
+    options: &WakeOptions,
+) -> Vec<(Vec<Ipv4Net>, Vec<u8>)> {
+    targets
+        .iter()
+        .map(|target| {
+            let destinations = match target.ip.or(options.host()) {
+                Some(ip) => vec![Ipv4Net::from(ip)],
+                None => networks.to_vec(),
+            };
+            let packet = match options.password() {
+                Some(password) => build_magic_packet_with_password(&target.mac, password),
+                None => build_magic_packet(&target.mac),
+            };
+            (destinations, packet)
+        })
+        .collect()
+}
+
+pub(crate) fn started(
+    sends: &[(Vec<Ipv4Net>, Vec<u8>)],
+    options: &WakeOptions,
+) -> WakeEvent<'static> {
+    WakeEvent::Started {
+        targets: sends.len(),
+        sends: sends
+            .iter()
+            .map(|(networks, _)| networks.len())
+            .sum::<usize>()
+            * options.ports().len(),
+    }
+}
+
+/// One send of a wake, made by [`send_steps`].
+pub struct SendStep<'a> {
+    pub target: &'a Target,
+    pub packet: &'a [u8],
+    /// The network to broadcast on, or the address to unicast to as a /32
+    /// network.
+    pub network: &'a Ipv4Net,
+    pub port: u16,
+    /// 1 for the first send, up to the number of repeats.
+    pub attempt: u32,
+    // where the result goes in the results of the first attempt
+    index: usize,
+}
+
+impl SendStep<'_> {
+    /// Whether this is the first send of a repeat, which comes after the
+    /// delay between repeats.
+    pub fn pauses_before(&self) -> bool {
+        self.attempt > 1 && self.index == 0
+    }
+}
+
+/// The sends of a wake in the order [`wake_all`] makes them, for a sender
+/// with its own socket handling, like the `wol` command line tool.
+///
+/// `sends` holds the networks and the packet of every target. Every target
+/// is sent to on each of its networks and `ports`, then the same again until
+/// `repeat` attempts are made. Wait for the delay between repeats before a
+/// step that [`pauses_before`](SendStep::pauses_before), and keep the results
+/// with [`record_send`].
+pub fn send_steps<'a>(
+    targets: &'a [Target],
+    sends: &'a [(Vec<Ipv4Net>, Vec<u8>)],
+    ports: &'a [u16],
+    repeat: u32,
+) -> impl Iterator<Item = SendStep<'a>> + 'a {
+    (1..=repeat).flat_map(move |attempt| {
+        targets
+            .iter()
+            .zip(sends)
+            .flat_map(move |(target, (networks, packet))| {
+                networks.iter().flat_map(move |network| {
+                    ports
+                        .iter()
+                        .map(move |port| (target, packet, network, *port))
+                })
+            })
+            .enumerate()
+            .map(move |(index, (target, packet, network, port))| SendStep {
+                target,
+                packet,
+                network,
+                port,
+                attempt,
+                index,
+            })
+    })
+}
+
+/// Reports the result of `step` as [`WakeEvent::Sent`] and keeps it in
+/// `results`, one per target, network and port in the order of
+/// [`send_steps`]. The first attempt adds the result, a repeat only replaces
+/// a failure.
+pub fn record_send(
+    results: &mut Vec<NetworkResult>,
+    step: &SendStep,
+    result: NetworkResult,
+    on_event: &mut impl FnMut(WakeEvent),
+) {
+    on_event(WakeEvent::Sent {
+        target: step.target,
+        result: &result,
+        attempt: step.attempt,
+    });
+    if step.attempt == 1 {
+        results.push(result);
+    } else if results[step.index].result.is_err() {
+        results[step.index] = result;
+    }
+}
+
+pub(crate) fn finished(results: &[NetworkResult], start: Instant) -> WakeEvent<'static> {
+    let failed = results
+        .iter()
+        .filter(|net_result| net_result.result.is_err())
+        .count();
+    WakeEvent::Finished {
+        sent: results.len() - failed,
+        failed,
+        elapsed: start.elapsed(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mac::MacAddr;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn reports_every_send_between_start_and_finish() {
+        let mut target = Target::new(MacAddr::new([0x00, 0x11, 0x22, 0x33, 0x44, 0x55]));
+        target.ip = Some(Ipv4Addr::LOCALHOST);
+        let options = WakeOptions::builder()
+            .port(7)
+            .port(9)
+            .repeat(2)
+            .delay(Duration::ZERO)
+            .build()
+            .unwrap();
+
+        let mut events = Vec::new();
+        let results = wake_all(&[target], &options, &mut |event| {
+            events.push(match event {
+                WakeEvent::Started { sends, .. } => format!("started {}", sends),
+                WakeEvent::Sent {
+                    result, attempt, ..
+                } => format!("sent {} {}", result.port, attempt),
+                WakeEvent::Finished { sent, failed, .. } => format!("finished {} {}", sent, failed),
+            })
+        })
+        .unwrap();
+
+        assert_eq!(
+            events,
+            [
+                "started 2",
+                "sent 7 1",
+                "sent 9 1",
+                "sent 7 2",
+                "sent 9 2",
+                "finished 2 0"
+            ]
+        );
+        assert_eq!(results.len(), 2);
+        assert_eq!(
+            results[0].network,
+            "127.0.0.1/32".parse::<Ipv4Net>().unwrap()
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::signal;
+
+// call `on_change` every time `path` is created, modified or removed, until
+// Ctrl-C or `deadline`. A burst of events only counts once it has been quiet
+// for `debounce`. The parent directory is watched, so `path` doesn't have to
+// exist yet and keeps being watched when it is removed and created again.
+// The entries of a directory that exists at the start are watched too
+pub fn watch(
+    path: &Path,
+    debounce: Duration,
+    deadline: Option<Instant>,
+    mut on_change: impl FnMut(),
+) -> notify::Result<()> {
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    let parent = match path.parent() {
+        Some(v) if !v.as_os_str().is_empty() => v,
+        _ => Path::new("."),
+    };
+    watcher.watch(parent, RecursiveMode::NonRecursive)?;
+    if path.is_dir() {
+        watcher.watch(path, RecursiveMode::NonRecursive)?;
+    }
+    // the other entries of the parent directory don't trigger a wake
+    let concerns_path = |event: &notify::Event| {
+        event.paths.iter().any(|event_path| {
+            event_path.file_name() == path.file_name() || event_path.parent() == Some(path)
+        })
+    };
+    signal::install();
+
+    let tick = Duration::from_millis(100);
+    let stopped = || signal::interrupted() || deadline.is_some_and(|d| Instant::now() >= d);
+    while !stopped() {
+        let event = match receiver.recv_timeout(tick) {
+            Ok(Ok(v)) => v,
+            Ok(Err(err)) => {
+                println!("Warning: watching {:?} failed: {}", path, err);
+                continue;
+            }
+            Err(_) => continue,
+        };
+        // opening or reading the file doesn't trigger a wake
+        if matches!(event.kind, EventKind::Access(_)) || !concerns_path(&event) {
+            continue;
+        }
+
+        let mut last_event = Instant::now();
+        while last_event.elapsed() < debounce {
+            if stopped() {
+                return Ok(());
+            }
+            if let Ok(Ok(event)) = receiver.recv_timeout(tick.min(debounce)) {
+                if concerns_path(&event) {
+                    last_event = Instant::now();
+                }
+            }
+        }
+        on_change();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::thread;
+
+    #[test]
+    fn wakes_when_the_file_is_created_removed_and_created_again() {
+        let dir = std::env::temp_dir().join(format!("wol-test-{}-watch", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let sentinel = dir.join("sentinel");
+        let _ = fs::remove_file(&sentinel);
+
+        let deadline = Instant::now() + Duration::from_secs(3);
+        let watched = sentinel.clone();
+        let watcher = thread::spawn(move || {
+            let mut changes = 0;
+            watch(&watched, Duration::from_millis(50), Some(deadline), || {
+                changes += 1
+            })
+            .unwrap();
+            changes
+        });
+
+        let settle = Duration::from_millis(500);
+        thread::sleep(settle);
+        fs::write(&sentinel, b"").unwrap();
+        thread::sleep(settle);
+        fs::remove_file(&sentinel).unwrap();
+        thread::sleep(settle);
+        // another file in the directory doesn't count
+        fs::write(dir.join("other"), b"").unwrap();
+        thread::sleep(settle);
+        fs::write(&sentinel, b"").unwrap();
+
+        assert_eq!(watcher.join().unwrap(), 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}